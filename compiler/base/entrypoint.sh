@@ -2,10 +2,21 @@
 
 set -eu
 
-timeout=${PLAYGROUND_TIMEOUT:-10}
+job=/playground-job.toml
 
-modify-cargo-toml
+if [[ -f "${job}" ]]; then
+    # Applies the job spec to Cargo.toml and prints the timeout and
+    # the command to run, each word terminated by a NUL
+    modify-cargo-toml "${job}" > /tmp/playground-job
+    mapfile -d '' command < /tmp/playground-job
+    timeout=${command[0]}
+    command=("${command[@]:1}")
+else
+    # Commands that don't need a job, such as checking versions
+    timeout=10
+    command=("$@")
+fi
 
 # Don't use `exec` here. The shell is what prints out the useful
 # "Killed" message
-timeout --signal=KILL ${timeout} "$@"
+timeout --signal=KILL ${timeout} "${command[@]}"
@@ -0,0 +1,109 @@
+// The job spec that the playground server writes for each run. The
+// version is checked on its own first so that a server and an image
+// that have drifted apart fail with a clear message instead of a
+// confusing parse error.
+
+use std::collections::BTreeMap;
+use toml::Value;
+
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Deserialize)]
+struct Versioned {
+    version: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct JobSpec {
+    /// Seconds before the command is killed
+    pub timeout: u64,
+    pub edition: Option<String>,
+    pub crate_type: Option<String>,
+    #[serde(default)]
+    pub no_dependencies: bool,
+    #[serde(default)]
+    pub release_lto: bool,
+    #[serde(default)]
+    pub wasm_bindgen: bool,
+    /// Where the source of the procedural macro crate is
+    pub proc_macro: Option<String>,
+    /// A partial Cargo.toml, already checked by the server
+    pub manifest: Option<String>,
+    #[serde(default)]
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub environment: BTreeMap<String, String>,
+    pub profile: Option<Profile>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    #[serde(default)]
+    pub settings: BTreeMap<String, Value>,
+}
+
+pub fn parse(spec: &str) -> JobSpec {
+    let Versioned { version } =
+        toml::from_str(spec).unwrap_or_else(|e| panic!("Cannot parse the job spec: {}", e));
+
+    if version != VERSION {
+        panic!(
+            "The job spec is version {}, but this image only understands version {}",
+            version, VERSION
+        );
+    }
+
+    toml::from_str(spec).unwrap_or_else(|e| panic!("Cannot parse the job spec: {}", e))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn spec_parsed() {
+        let spec = parse(
+            r#"
+            version = 1
+            timeout = 10
+            edition = "2021"
+            command = ["cargo", "test", "--", "passes"]
+
+            [environment]
+            RUST_BACKTRACE = "1"
+
+            [profile]
+            name = "release"
+
+            [profile.settings]
+            opt-level = "s"
+            "#,
+        );
+
+        assert_eq!(spec.timeout, 10);
+        assert_eq!(spec.edition.as_deref(), Some("2021"));
+        assert!(!spec.no_dependencies);
+        assert_eq!(spec.command, ["cargo", "test", "--", "passes"]);
+        assert_eq!(spec.environment["RUST_BACKTRACE"], "1");
+
+        let profile = spec.profile.unwrap();
+        assert_eq!(profile.name, "release");
+        assert_eq!(profile.settings["opt-level"].as_str(), Some("s"));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "The job spec is version 2, but this image only understands version 1"
+    )]
+    fn mismatched_version_rejected() {
+        parse("version = 2\ntimeout = 10\nsomething-new = true");
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot parse the job spec")]
+    fn missing_version_rejected() {
+        parse("timeout = 10");
+    }
+}
@@ -3,43 +3,173 @@ extern crate serde;
 extern crate serde_derive;
 extern crate toml;
 
-use std::{collections::BTreeMap, env, ffi::OsString, fs, path::PathBuf};
+mod job;
+
+use job::JobSpec;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env,
+    ffi::OsString,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
 use toml::Value;
 
+// Where the playground server places the job spec
+const JOB_SPEC_FILE: &str = "/playground-job.toml";
+
+// The name that code in the playground uses to refer to the
+// procedural macro crate
+const PROC_MACRO_CRATE: &str = "playground_macros";
+const PROC_MACRO_DIR: &str = "proc-macro";
+
+// The dependencies that procedural macros commonly rely on
+const PROC_MACRO_DEPENDENCIES: &[&str] = &["proc_macro2", "quote", "syn"];
+
 fn main() {
     let mut args = env::args_os().skip(1).fuse();
 
+    let job_filename = args.next().unwrap_or_else(|| OsString::from(JOB_SPEC_FILE));
     let input_filename = args.next().unwrap_or_else(|| OsString::from("Cargo.toml"));
     let output_filename = args.next().unwrap_or_else(|| input_filename.clone());
 
+    let job_filename = PathBuf::from(job_filename);
     let input_filename = PathBuf::from(input_filename);
     let output_filename = PathBuf::from(output_filename);
 
+    let job = fs::read_to_string(&job_filename)
+        .unwrap_or_else(|e| panic!("Cannot read {}: {}", job_filename.display(), e));
+    let JobSpec {
+        timeout,
+        edition,
+        crate_type,
+        no_dependencies,
+        release_lto,
+        wasm_bindgen,
+        proc_macro,
+        manifest,
+        command,
+        environment,
+        profile,
+    } = job::parse(&job);
+
     let input = fs::read_to_string(&input_filename)
         .unwrap_or_else(|e| panic!("Cannot read {}: {}", input_filename.display(), e));
     let mut cargo_toml: Value = toml::from_str(&input)
         .unwrap_or_else(|e| panic!("Cannot parse {} as TOML: {}", input_filename.display(), e));
 
-    if let Ok(edition) = env::var("PLAYGROUND_EDITION") {
+    // Captured before any dependencies might be removed
+    let proc_macro = proc_macro.map(|source| (source, proc_macro_dependencies(&cargo_toml)));
+
+    let aliases = aliases(&cargo_toml);
+
+    if let Some(edition) = edition {
         cargo_toml = set_edition(cargo_toml, &edition);
     }
 
-    if env::var_os("PLAYGROUND_NO_DEPENDENCIES").is_some() {
+    if no_dependencies {
         cargo_toml = remove_dependencies(cargo_toml);
     }
 
-    if let Ok(crate_type) = env::var("PLAYGROUND_CRATE_TYPE") {
+    if wasm_bindgen {
+        // The version is a property of the image, not of the job
+        if let Ok(version) = env::var("PLAYGROUND_WASM_BINDGEN_VERSION") {
+            let version = format!("={}", version);
+            cargo_toml = add_dependency(cargo_toml, "wasm-bindgen", Value::String(version));
+        }
+    }
+
+    if let Some(crate_type) = crate_type {
         cargo_toml = set_crate_type(cargo_toml, &crate_type);
     }
 
-    if let Ok(lto) = env::var("PLAYGROUND_RELEASE_LTO") {
-        cargo_toml = set_release_lto(cargo_toml, lto == "true");
+    if release_lto {
+        cargo_toml = set_release_lto(cargo_toml, true);
+    }
+
+    if let Some(profile) = profile {
+        cargo_toml = set_profile(cargo_toml, &profile.name, profile.settings);
+    }
+
+    // Applied last so that the request's own settings win
+    if let Some(manifest) = manifest {
+        cargo_toml = merge_manifest(cargo_toml, &manifest, &aliases);
+    }
+
+    if let Some((source, dependencies)) = proc_macro {
+        let dir = output_filename
+            .parent()
+            .map_or_else(|| PathBuf::from(PROC_MACRO_DIR), |p| p.join(PROC_MACRO_DIR));
+        let proc_macro_toml = proc_macro_cargo_toml(&cargo_toml, &source, dependencies);
+        let proc_macro_toml =
+            toml::to_string(&proc_macro_toml).expect("Cannot convert back to TOML");
+
+        fs::create_dir_all(&dir)
+            .unwrap_or_else(|e| panic!("Cannot create {}: {}", dir.display(), e));
+        let proc_macro_filename = dir.join("Cargo.toml");
+        fs::write(&proc_macro_filename, proc_macro_toml)
+            .unwrap_or_else(|e| panic!("Cannot write to {}: {}", proc_macro_filename.display(), e));
+
+        let mut path = BTreeMap::new();
+        path.insert("path".to_string(), Value::String(PROC_MACRO_DIR.into()));
+        cargo_toml = add_dependency(cargo_toml, PROC_MACRO_CRATE, Value::try_from(path).unwrap());
+    }
+
+    // Code shared from the playground may still use ids that crates
+    // are no longer known by
+    let aliases = available_aliases(&cargo_toml, aliases);
+    if !aliases.is_empty() {
+        let src = input_filename
+            .parent()
+            .map_or_else(|| PathBuf::from("src"), |p| p.join("src"));
+
+        for source in &[src.join("main.rs"), src.join("lib.rs")] {
+            declare_aliases_in_file(source, &aliases);
+        }
     }
 
     let output = toml::to_string(&cargo_toml).expect("Cannot convert back to TOML");
 
     fs::write(&output_filename, output)
         .unwrap_or_else(|e| panic!("Cannot write to {}: {}", output_filename.display(), e));
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for word in command_words(timeout, environment, command) {
+        write!(stdout, "{}\0", word).expect("Cannot write the command");
+    }
+}
+
+// The entrypoint runs these words under `timeout`, so each is
+// terminated by a NUL to survive any characters they contain. A NUL
+// inside a word would split it into more arguments, so that's refused.
+fn command_words(
+    timeout: u64,
+    environment: BTreeMap<String, String>,
+    command: Vec<String>,
+) -> Vec<String> {
+    for (name, value) in &environment {
+        if name.is_empty() || name.contains('=') || name.contains('\0') || value.contains('\0') {
+            panic!(
+                "The environment variable {:?} cannot be passed to the command",
+                name
+            );
+        }
+    }
+    if let Some(word) = command.iter().find(|word| word.contains('\0')) {
+        panic!("The command word {:?} contains a NUL byte", word);
+    }
+
+    let environment = environment
+        .into_iter()
+        .map(|(name, value)| format!("{}={}", name, value));
+    Some(timeout.to_string())
+        .into_iter()
+        .chain(Some("env".to_string()))
+        .chain(environment)
+        .chain(command)
+        .collect()
 }
 
 type Other = BTreeMap<String, Value>;
@@ -101,6 +231,22 @@ fn remove_dependencies(cargo_toml: Value) -> Value {
     })
 }
 
+fn add_dependency(cargo_toml: Value, name: &str, dependency: Value) -> Value {
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct CargoToml {
+        #[serde(default)]
+        dependencies: BTreeMap<String, Value>,
+        #[serde(flatten)]
+        other: Other,
+    }
+
+    modify(cargo_toml, |mut cargo_toml: CargoToml| {
+        cargo_toml.dependencies.insert(name.into(), dependency);
+        cargo_toml
+    })
+}
+
 fn set_crate_type(cargo_toml: Value, crate_type: &str) -> Value {
     #[derive(Debug, Serialize, Deserialize)]
     #[serde(rename_all = "kebab-case")]
@@ -159,3 +305,392 @@ fn set_release_lto(cargo_toml: Value, lto: bool) -> Value {
         cargo_toml
     })
 }
+
+fn set_profile(mut cargo_toml: Value, profile: &str, settings: Other) -> Value {
+    let package = package_name(&cargo_toml);
+    merge(
+        &mut cargo_toml,
+        package_profile(&package, profile, settings),
+    );
+    cargo_toml
+}
+
+// Settings for the whole profile would also apply to every
+// preinstalled crate, which would then all need to be rebuilt. Cargo
+// allows most settings to be given for one package instead.
+fn package_profile(package: &str, profile: &str, settings: Other) -> Value {
+    use toml::value::Table;
+
+    let mut packages = Table::new();
+    packages.insert(package.into(), Value::Table(settings.into_iter().collect()));
+    let mut overrides = Table::new();
+    overrides.insert("package".into(), Value::Table(packages));
+    let mut profiles = Table::new();
+    profiles.insert(profile.into(), Value::Table(overrides));
+    let mut cargo_toml = Table::new();
+    cargo_toml.insert("profile".into(), Value::Table(profiles));
+    Value::Table(cargo_toml)
+}
+
+fn package_name(cargo_toml: &Value) -> String {
+    cargo_toml
+        .get("package")
+        .and_then(|p| p.get("name"))
+        .and_then(Value::as_str)
+        .expect("The package has no name")
+        .into()
+}
+
+// The manifest has already been limited to settings that are safe to
+// change; the only thing left to check is that every dependency it
+// mentions is one of the crates that is already available. Like the
+// request's profile options, its profile settings only apply to the
+// playground crate.
+fn merge_manifest(mut cargo_toml: Value, manifest: &str, aliases: &Aliases) -> Value {
+    let mut manifest: Value = toml::from_str(manifest)
+        .unwrap_or_else(|e| panic!("Cannot parse the requested manifest as TOML: {}", e));
+
+    let profiles = manifest
+        .as_table_mut()
+        .and_then(|manifest| manifest.remove("profile"));
+    if let Some(Value::Table(profiles)) = profiles {
+        for (profile, settings) in profiles {
+            match settings {
+                Value::Table(settings) => {
+                    let settings = settings.into_iter().collect();
+                    cargo_toml = set_profile(cargo_toml, &profile, settings);
+                }
+                _ => panic!("The profile {} must be a table", profile),
+            }
+        }
+    }
+
+    if let Some(requested) = manifest
+        .get_mut("dependencies")
+        .and_then(Value::as_table_mut)
+    {
+        let renamed = requested
+            .keys()
+            .filter(|name| aliases.contains_key(*name))
+            .cloned()
+            .collect::<Vec<_>>();
+        for name in renamed {
+            let dependency = requested.remove(&name).unwrap();
+            requested.insert(aliases[&name].clone(), dependency);
+        }
+    }
+
+    if let Some(requested) = manifest
+        .get_mut("dependencies")
+        .and_then(Value::as_table_mut)
+    {
+        let available = cargo_toml
+            .get_mut("dependencies")
+            .and_then(Value::as_table_mut)
+            .expect("No crates are available");
+
+        for (name, dependency) in requested.iter_mut() {
+            let existing = available
+                .get_mut(name)
+                .unwrap_or_else(|| panic!("The crate {} is not available", name));
+
+            // Only the existing version can be used
+            if let Value::String(version) = existing.clone() {
+                let mut table = toml::value::Table::new();
+                table.insert("version".into(), Value::String(version));
+                *existing = Value::Table(table);
+            }
+            if dependency.is_str() {
+                *dependency = Value::Table(toml::value::Table::new());
+            }
+
+            // The crate has already been built with its features, so
+            // any that are requested are added to them
+            let features = existing.get("features").and_then(Value::as_array).cloned();
+            let requested = dependency.get_mut("features").and_then(Value::as_array_mut);
+            if let (Some(mut features), Some(requested)) = (features, requested) {
+                for feature in requested.drain(..) {
+                    if !features.contains(&feature) {
+                        features.push(feature);
+                    }
+                }
+                *requested = features;
+            }
+        }
+    }
+
+    merge(&mut cargo_toml, manifest);
+    cargo_toml
+}
+
+type Aliases = BTreeMap<String, String>;
+
+fn aliases(cargo_toml: &Value) -> Aliases {
+    cargo_toml
+        .get("package")
+        .and_then(|p| p.get("metadata"))
+        .and_then(|m| m.get("playground"))
+        .and_then(|p| p.get("aliases"))
+        .and_then(Value::as_table)
+        .map(|aliases| {
+            aliases
+                .iter()
+                .filter_map(|(alias, id)| Some((alias.clone(), id.as_str()?.to_string())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Aliases for crates that are no longer dependencies, such as when
+// they have all been removed, cannot be declared
+fn available_aliases(cargo_toml: &Value, mut aliases: Aliases) -> Aliases {
+    let dependencies = cargo_toml.get("dependencies");
+    aliases.retain(|_, id| dependencies.and_then(|d| d.get(id.as_str())).is_some());
+    aliases
+}
+
+fn declare_aliases_in_file(path: &Path, aliases: &Aliases) {
+    let mut source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return,
+        Err(e) => panic!("Cannot read {}: {}", path.display(), e),
+    };
+
+    if let Some(declarations) = alias_declarations(&source, aliases) {
+        source.push_str(&declarations);
+        fs::write(path, source)
+            .unwrap_or_else(|e| panic!("Cannot write to {}: {}", path.display(), e));
+    }
+}
+
+// The user's code is left as it is; each alias that it mentions is
+// declared as a crate of its own after the last line, which keeps
+// every diagnostic pointing at the same line and column. Returns
+// `None` when no alias is mentioned.
+fn alias_declarations(source: &str, aliases: &Aliases) -> Option<String> {
+    let is_ident = |c: char| c == '_' || c.is_alphanumeric();
+
+    let used: BTreeSet<&str> = source
+        .split(|c| !is_ident(c))
+        .filter(|word| aliases.contains_key(*word))
+        .collect();
+    if used.is_empty() {
+        return None;
+    }
+
+    let mut declarations = String::from("\n");
+    for alias in used {
+        declarations.push_str(&format!("extern crate {} as {};\n", aliases[alias], alias));
+    }
+    Some(declarations)
+}
+
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Table(base), Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn proc_macro_dependencies(cargo_toml: &Value) -> BTreeMap<String, Value> {
+    let dependencies = cargo_toml.get("dependencies").and_then(Value::as_table);
+
+    PROC_MACRO_DEPENDENCIES
+        .iter()
+        .filter_map(|&name| {
+            let dependency = dependencies?.get(name)?;
+            Some((name.to_string(), dependency.clone()))
+        })
+        .collect()
+}
+
+// The procedural macro crate uses the same edition as the playground
+// crate and reads its source from outside of its own directory
+fn proc_macro_cargo_toml(
+    cargo_toml: &Value,
+    source: &str,
+    dependencies: BTreeMap<String, Value>,
+) -> Value {
+    #[derive(Debug, Serialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct CargoToml {
+        package: Package,
+        lib: Lib,
+        dependencies: BTreeMap<String, Value>,
+    }
+
+    #[derive(Debug, Serialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct Package {
+        name: String,
+        version: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        edition: Option<String>,
+    }
+
+    #[derive(Debug, Serialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct Lib {
+        path: String,
+        proc_macro: bool,
+    }
+
+    let edition = cargo_toml
+        .get("package")
+        .and_then(|p| p.get("edition"))
+        .and_then(Value::as_str)
+        .filter(|e| !e.is_empty())
+        .map(Into::into);
+
+    let proc_macro_toml = CargoToml {
+        package: Package {
+            name: PROC_MACRO_CRATE.into(),
+            version: "0.0.1".into(),
+            edition,
+        },
+        lib: Lib {
+            path: source.into(),
+            proc_macro: true,
+        },
+        dependencies,
+    };
+
+    Value::try_from(proc_macro_toml).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rand_aliases() -> Aliases {
+        let mut aliases = Aliases::new();
+        aliases.insert("rand_0_7_3".into(), "rand_0_7".into());
+        aliases
+    }
+
+    #[test]
+    fn aliases_declared_after_the_code() {
+        let source = "use rand_0_7_3::Rng;\nlet my_rand_0_7_3 = rand_0_7_3::random::<u8>();";
+
+        assert_eq!(
+            alias_declarations(source, &rand_aliases()).unwrap(),
+            "\nextern crate rand_0_7 as rand_0_7_3;\n",
+        );
+        assert_eq!(alias_declarations("use rand::Rng;", &rand_aliases()), None);
+    }
+
+    #[test]
+    fn aliases_of_removed_crates_ignored() {
+        let cargo_toml = toml::from_str("[dependencies]\nrand = \"0.8\"").unwrap();
+
+        assert!(available_aliases(&cargo_toml, rand_aliases()).is_empty());
+    }
+
+    #[test]
+    fn command_words_include_environment() {
+        let mut environment = BTreeMap::new();
+        environment.insert("RUST_BACKTRACE".to_string(), "1".to_string());
+        let command = vec!["cargo".to_string(), "run".to_string()];
+
+        assert_eq!(
+            command_words(10, environment, command),
+            ["10", "env", "RUST_BACKTRACE=1", "cargo", "run"],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "contains a NUL byte")]
+    fn command_words_with_nul_rejected() {
+        let command = vec!["cargo".to_string(), "test\0--nocapture".to_string()];
+        command_words(10, BTreeMap::new(), command);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot be passed to the command")]
+    fn environment_with_nul_rejected() {
+        let mut environment = BTreeMap::new();
+        environment.insert("RUSTFLAGS".to_string(), "-C\0opt-level=3".to_string());
+        command_words(10, environment, Vec::new());
+    }
+
+    #[test]
+    fn aliases_read_from_metadata() {
+        let cargo_toml = toml::from_str(
+            r#"
+            [package.metadata.playground.aliases]
+            rand_0_7_3 = "rand_0_7"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(aliases(&cargo_toml), rand_aliases());
+    }
+
+    fn playground_toml() -> Value {
+        toml::from_str(
+            r#"
+            [package]
+            name = "playground"
+
+            [dependencies]
+            rand = "0.7"
+            serde = { version = "1", features = ["derive"] }
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn profile_set_for_the_playground_crate() {
+        let mut settings = Other::new();
+        settings.insert("opt-level".into(), Value::String("s".into()));
+
+        let cargo_toml = set_profile(playground_toml(), "release", settings);
+
+        assert_eq!(
+            cargo_toml["profile"]["release"]["package"]["playground"]["opt-level"].as_str(),
+            Some("s"),
+        );
+        assert!(cargo_toml["profile"]["release"].get("opt-level").is_none());
+    }
+
+    #[test]
+    fn manifest_features_added_to_existing_ones() {
+        let manifest = r#"
+            [profile.dev]
+            overflow-checks = false
+
+            [dependencies]
+            rand = { features = ["small_rng"] }
+            serde = { features = ["rc"] }
+        "#;
+
+        let cargo_toml = merge_manifest(playground_toml(), manifest, &Aliases::new());
+
+        assert_eq!(
+            cargo_toml["profile"]["dev"]["package"]["playground"]["overflow-checks"].as_bool(),
+            Some(false),
+        );
+        assert_eq!(
+            cargo_toml["dependencies"]["rand"],
+            toml::from_str::<Value>(
+                r#"version = "0.7"
+features = ["small_rng"]"#
+            )
+            .unwrap(),
+        );
+        let features = cargo_toml["dependencies"]["serde"]["features"]
+            .as_array()
+            .unwrap();
+        assert_eq!(features, &[Value::from("derive"), Value::from("rc")]);
+    }
+}
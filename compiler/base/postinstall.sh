@@ -4,6 +4,7 @@ set -eu
 
 function install_wasm_target() {
     rustup target add wasm32-unknown-unknown
+    rustup target add wasm32-wasi
 }
 
 function install_wasm2wat() {
@@ -20,8 +21,26 @@ function install_wasm_gc() {
     cargo install wasm-gc
 }
 
+# The generated glue only works with the exact library version the
+# tool was built for, so fetch that version for offline use.
+function install_wasm_bindgen() {
+    cargo install wasm-bindgen-cli --version "${PLAYGROUND_WASM_BINDGEN_VERSION}"
+
+    cargo new --lib /tmp/fetch-wasm-bindgen
+    cd /tmp/fetch-wasm-bindgen
+    echo "wasm-bindgen = \"=${PLAYGROUND_WASM_BINDGEN_VERSION}\"" >> Cargo.toml
+    cargo fetch
+    rm -rf /tmp/fetch-wasm-bindgen
+}
+
+function install_wasmtime() {
+    cargo install wasmtime-cli
+}
+
 if [[ $1 == "nightly" ]]; then
     (install_wasm_target)
     (install_wasm2wat)
     (install_wasm_gc)
+    (install_wasm_bindgen)
+    (install_wasmtime)
 fi
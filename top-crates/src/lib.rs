@@ -6,18 +6,25 @@ use cargo::{
         package::PackageSet,
         registry::PackageRegistry,
         resolver::{self, features::RequestedFeatures, ResolveOpts},
-        source::SourceMap,
-        Dependency, Package, Source, SourceId, TargetKind,
+        source::{GitReference, SourceMap},
+        Dependency, Package, Shell, SourceId, TargetKind,
     },
-    sources::RegistrySource,
-    util::{Config, VersionExt},
+    sources::SourceConfigMap,
+    util::{homedir, interning::InternedString, Config, IntoUrl, VersionExt},
 };
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::{BTreeMap, BTreeSet, HashSet},
+    fs,
     io::Read,
+    path::{Path, PathBuf},
+    rc::Rc,
 };
+use tempfile::TempDir;
+
+pub mod report;
+pub mod verify;
 
 const PLAYGROUND_TARGET_PLATFORM: &str = "x86_64-unknown-linux-gnu";
 
@@ -32,14 +39,71 @@ struct TopCrates {
 struct Crate {
     #[serde(rename = "id")]
     name: String,
+    #[serde(skip)]
+    source: CrateSource,
 }
 
-/// A mapping of a crates name to its identifier used in source code
+/// Why a crate was requested
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CrateSource {
+    #[default]
+    TopDownloads,
+    Cookbook,
+    Addition,
+}
+
+/// A mapping of a crates name to its identifier used in source code,
+/// along with details to help people find the crate they want
 #[derive(Debug, Serialize)]
 pub struct CrateInformation {
     pub name: String,
     pub version: String,
     pub id: String,
+    pub description: Option<String>,
+    pub documentation: Option<String>,
+    pub repository: Option<String>,
+    pub license: Option<String>,
+    /// The features enabled after resolving every crate together
+    pub features: Vec<String>,
+    pub available_features: Vec<String>,
+    /// Empty for crates that are only present as a dependency of
+    /// another crate
+    pub sources: BTreeSet<CrateSource>,
+    /// Other ids that code may use to refer to this version, such as
+    /// `rand_0_7_3` from when ids contained the full version
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+/// Ids that are no longer generated but that shared code may still
+/// use. Each names a crate and a version requirement instead of an id
+/// so that it keeps working as new versions are released.
+#[derive(Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Aliases(BTreeMap<String, AliasTarget>);
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AliasTarget {
+    pub package: String,
+    /// A version requirement, such as `"0.7"`
+    pub version: String,
+}
+
+/// Where the generator reads its inputs from. Anything that isn't
+/// provided is fetched from the internet.
+#[derive(Debug, Default)]
+pub struct Inputs {
+    /// A saved response from the crates.io API listing the crates with
+    /// the most downloads
+    pub top_crates: Option<PathBuf>,
+    /// A saved copy of the Rust Cookbook's Cargo.toml
+    pub cookbook_manifest: Option<PathBuf>,
+    /// A local registry, such as one created by `cargo local-registry`,
+    /// that replaces crates.io. Cargo will not access the network when
+    /// this is set.
+    pub local_registry: Option<PathBuf>,
 }
 
 /// Hand-curated changes to the crate list
@@ -49,6 +113,54 @@ pub struct Modifications {
     pub exclusions: Vec<String>,
     #[serde(default)]
     pub additions: BTreeSet<String>,
+    /// Alternative registries by name, each with the URL of its index
+    #[serde(default)]
+    pub registries: BTreeMap<String, String>,
+    /// Crates to add that don't come from crates.io
+    #[serde(default)]
+    pub sources: BTreeMap<String, AlternativeSource>,
+    /// Changes to how individual crates are included
+    #[serde(default)]
+    pub crates: BTreeMap<String, CrateModification>,
+}
+
+/// Overrides for a single crate. These only take effect when the crate
+/// is included by some other means, such as being in `additions`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct CrateModification {
+    /// A version requirement, such as `"0.7"`, to use instead of
+    /// the newest version. The matching version keeps the plain id
+    /// even when a dependency pulls in a newer one.
+    pub version: Option<String>,
+    /// Features to enable in every included version, in addition to
+    /// those from the crate's playground metadata. Each version must
+    /// have all of them.
+    pub features: Vec<String>,
+    /// Replaces the crate's own `default-features` choice for every
+    /// included version
+    pub default_features: Option<bool>,
+    /// Requirements for more versions to include alongside the main
+    /// one, such as `"0.7"`, which is exposed as `rand_0_7`. Each must
+    /// be semver-incompatible with every other version.
+    pub also_include: Vec<String>,
+}
+
+/// Where to find a crate that isn't on crates.io. Exactly one of
+/// `registry`, `git` or `path` must be set.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct AlternativeSource {
+    /// The name of one of the `registries`
+    pub registry: Option<String>,
+    pub git: Option<String>,
+    pub branch: Option<String>,
+    pub tag: Option<String>,
+    pub rev: Option<String>,
+    /// An absolute path. The crate is copied into the container's
+    /// build context, so its own `path` dependencies must be listed
+    /// here too and may not refer to each other by relative paths.
+    pub path: Option<PathBuf>,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -62,6 +174,18 @@ pub struct DependencySpec {
     pub features: Vec<String>,
     #[serde(skip_serializing_if = "is_true")]
     pub default_features: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
 }
 
 fn exact_version<S>(version: &String, serializer: S) -> Result<S::Ok, S::Error>
@@ -75,10 +199,176 @@ fn is_true(b: &bool) -> bool {
     *b
 }
 
+/// Whether Cargo treats the two versions as compatible, in which case
+/// only one of them can be a dependency
+fn semver_compatible(a: &semver::Version, b: &semver::Version) -> bool {
+    match (a.major, b.major) {
+        (0, 0) if a.minor == 0 && b.minor == 0 => a.patch == b.patch,
+        (0, 0) => a.minor == b.minor,
+        (a_major, b_major) => a_major == b_major,
+    }
+}
+
 impl Modifications {
     fn excluded(&self, name: &str) -> bool {
         self.exclusions.iter().any(|n| n == name)
     }
+
+    /// The version requirements to query for `name`; `None` means the
+    /// newest version
+    fn version_requirements(&self, name: &str) -> Vec<Option<&str>> {
+        match self.crates.get(name) {
+            Some(modification) => {
+                let also_include = modification.also_include.iter().map(|v| Some(&**v));
+                std::iter::once(modification.version.as_deref())
+                    .chain(also_include)
+                    .collect()
+            }
+            None => vec![None],
+        }
+    }
+
+    fn resolve_opts(&self, name: &str) -> ResolveOpts {
+        let modification = self.crates.get(name);
+
+        let features = modification
+            .map(|m| m.features.iter().map(|f| InternedString::new(f)).collect())
+            .unwrap_or_default();
+        let uses_default_features = modification
+            .and_then(|m| m.default_features)
+            .unwrap_or(true);
+
+        ResolveOpts {
+            dev_deps: false,
+            features: RequestedFeatures::DepFeatures {
+                features: Rc::new(features),
+                uses_default_features,
+            },
+        }
+    }
+
+    /// Applies the forced features and `default-features` choice on
+    /// top of what the crate's playground metadata asked for
+    fn override_features(&self, name: &str, features: &mut Vec<String>, default_features: &mut bool) {
+        let modification = match self.crates.get(name) {
+            Some(modification) => modification,
+            None => return,
+        };
+
+        for feature in &modification.features {
+            if !features.contains(feature) {
+                features.push(feature.clone());
+            }
+        }
+
+        if let Some(forced) = modification.default_features {
+            *default_features = forced;
+        }
+    }
+
+    fn registry_source_id(&self, registry: &str) -> SourceId {
+        let index = self
+            .registries
+            .get(registry)
+            .unwrap_or_else(|| panic!("The registry {} is not listed in `registries`", registry));
+        let url = index
+            .into_url()
+            .unwrap_or_else(|e| panic!("Invalid index URL for registry {}: {}", registry, e));
+
+        SourceId::for_alt_registry(&url, registry)
+            .unwrap_or_else(|e| panic!("Unable to create source ID for registry {}: {}", registry, e))
+    }
+
+    /// Where the crate named `name` should be queried from
+    fn source_id(&self, name: &str, crates_io: SourceId) -> SourceId {
+        let source = match self.sources.get(name) {
+            Some(source) => source,
+            None => return crates_io,
+        };
+
+        let id = match source {
+            AlternativeSource {
+                registry: Some(registry),
+                git: None,
+                path: None,
+                ..
+            } => Ok(self.registry_source_id(registry)),
+
+            AlternativeSource {
+                registry: None,
+                git: Some(git),
+                path: None,
+                branch,
+                tag,
+                rev,
+            } => {
+                let reference = match (branch, tag, rev) {
+                    (None, None, None) => GitReference::DefaultBranch,
+                    (Some(branch), None, None) => GitReference::Branch(branch.clone()),
+                    (None, Some(tag), None) => GitReference::Tag(tag.clone()),
+                    (None, None, Some(rev)) => GitReference::Rev(rev.clone()),
+                    _ => panic!("Only one of branch, tag or rev may be set for {}", name),
+                };
+                git.into_url()
+                    .and_then(|url| SourceId::for_git(&url, reference))
+            }
+
+            AlternativeSource {
+                registry: None,
+                git: None,
+                path: Some(path),
+                ..
+            } => {
+                assert!(path.is_absolute(), "The path for {} must be absolute", name);
+                SourceId::for_path(path)
+            }
+
+            _ => panic!("Exactly one of registry, git or path must be set for {}", name),
+        };
+
+        id.unwrap_or_else(|e| panic!("Unable to create source ID for {}: {}", name, e))
+    }
+
+    /// Records where a crate outside of crates.io comes from so the
+    /// generated Cargo.toml can find it
+    fn set_location(&self, spec: &mut DependencySpec, source_id: SourceId) {
+        if source_id.is_default_registry() {
+            return;
+        }
+
+        if source_id.is_registry() {
+            spec.registry = self
+                .registries
+                .keys()
+                .find(|registry| self.registry_source_id(registry) == source_id)
+                .cloned();
+        } else if source_id.is_git() {
+            spec.git = Some(source_id.url().to_string());
+            match source_id.git_reference() {
+                Some(GitReference::Branch(branch)) => spec.branch = Some(branch.clone()),
+                Some(GitReference::Tag(tag)) => spec.tag = Some(tag.clone()),
+                Some(GitReference::Rev(rev)) => spec.rev = Some(rev.clone()),
+                Some(GitReference::DefaultBranch) | None => {}
+            }
+        } else if let Some(path) = source_id.local_path() {
+            spec.path = Some(path.to_string_lossy().into_owned());
+        }
+    }
+}
+
+/// Loads and updates the source for `id` if it hasn't been already
+fn load_source<'cfg>(sources: &mut SourceMap<'cfg>, config_map: &SourceConfigMap<'cfg>, id: SourceId) {
+    if sources.contains(id) {
+        return;
+    }
+
+    let mut source = config_map
+        .load(id, &HashSet::new())
+        .unwrap_or_else(|e| panic!("Unable to load source {}: {}", id, e));
+    source
+        .update()
+        .unwrap_or_else(|e| panic!("Unable to update source {}: {}", id, e));
+    sources.insert(source);
 }
 
 fn simple_get(url: &str) -> reqwest::Result<reqwest::blocking::Response> {
@@ -90,6 +380,17 @@ fn simple_get(url: &str) -> reqwest::Result<reqwest::blocking::Response> {
 }
 
 impl TopCrates {
+    fn load(path: Option<&Path>) -> TopCrates {
+        match path {
+            Some(path) => {
+                let content = fs::read(path)
+                    .unwrap_or_else(|e| panic!("Could not read {}: {}", path.display(), e));
+                serde_json::from_slice(&content).expect("Invalid JSON")
+            }
+            None => TopCrates::download(),
+        }
+    }
+
     /// List top 100 crates by number of downloads on crates.io.
     fn download() -> TopCrates {
         let resp =
@@ -100,16 +401,12 @@ impl TopCrates {
         serde_json::from_reader(resp).expect("Invalid JSON")
     }
 
-    fn add_rust_cookbook_crates(&mut self) {
-        let mut resp = simple_get(
-            "https://raw.githubusercontent.com/rust-lang-nursery/rust-cookbook/master/Cargo.toml",
-        )
-        .expect("Could not fetch cookbook manifest");
-        assert!(resp.status().is_success(), "Could not download cookbook; HTTP status was {}", resp.status());
-
-        let mut content = String::new();
-        resp.read_to_string(&mut content)
-            .expect("could not read cookbook manifest");
+    fn add_rust_cookbook_crates(&mut self, path: Option<&Path>) {
+        let content = match path {
+            Some(path) => fs::read_to_string(path)
+                .unwrap_or_else(|e| panic!("Could not read {}: {}", path.display(), e)),
+            None => download_cookbook_manifest(),
+        };
 
         let manifest = content
             .parse::<toml::Value>()
@@ -121,6 +418,7 @@ impl TopCrates {
         self.crates.extend({
             dependencies.iter().map(|(name, _)| Crate {
                 name: name.to_string(),
+                source: CrateSource::Cookbook,
             })
         })
     }
@@ -131,12 +429,78 @@ impl TopCrates {
             modifications
                 .additions
                 .iter()
+                .chain(modifications.sources.keys())
                 .cloned()
-                .map(|name| Crate { name })
+                .map(|name| Crate {
+                    name,
+                    source: CrateSource::Addition,
+                })
         });
     }
 }
 
+fn download_cookbook_manifest() -> String {
+    let mut resp = simple_get(
+        "https://raw.githubusercontent.com/rust-lang-nursery/rust-cookbook/master/Cargo.toml",
+    )
+    .expect("Could not fetch cookbook manifest");
+    assert!(resp.status().is_success(), "Could not download cookbook; HTTP status was {}", resp.status());
+
+    let mut content = String::new();
+    resp.read_to_string(&mut content)
+        .expect("could not read cookbook manifest");
+    content
+}
+
+/// The Cargo configuration that replaces crates.io with the local
+/// registry at `path`
+fn local_registry_source(path: &Path) -> String {
+    let path = path
+        .canonicalize()
+        .unwrap_or_else(|e| panic!("Unable to find local registry {}: {}", path.display(), e));
+    let path = toml::Value::String(path.to_string_lossy().into_owned());
+
+    format!(
+        "[source.crates-io]\nreplace-with = \"playground-local\"\n\n[source.playground-local]\nlocal-registry = {}\n",
+        path,
+    )
+}
+
+/// Creates a Cargo configuration that uses a local registry in place
+/// of crates.io. Cargo only reads source replacement from
+/// configuration files, so this is written to one in a scratch
+/// directory which is then used as the working directory. The
+/// directory is removed when the returned `TempDir` is dropped, so it
+/// must be kept for as long as the `Config` is used:
+///
+/// ```toml
+/// [source.crates-io]
+/// replace-with = "playground-local"
+///
+/// [source.playground-local]
+/// local-registry = "/path/to/registry"
+/// ```
+fn local_registry_config(path: &Path) -> (Config, TempDir) {
+    let cwd = tempfile::Builder::new()
+        .prefix("playground-local-registry")
+        .tempdir()
+        .expect("Unable to create a directory for the Cargo configuration");
+    let config_dir = cwd.path().join(".cargo");
+    std::fs::create_dir_all(&config_dir)
+        .unwrap_or_else(|e| panic!("Unable to create {}: {}", config_dir.display(), e));
+
+    let config_path = config_dir.join("config.toml");
+    std::fs::write(&config_path, local_registry_source(path))
+        .unwrap_or_else(|e| panic!("Unable to write {}: {}", config_path.display(), e));
+
+    let homedir = homedir(cwd.path()).expect("Unable to find the Cargo home directory");
+    let mut config = Config::new(Shell::new(), cwd.path().to_owned(), homedir);
+    config
+        .configure(0, false, None, false, false, true, &None, &[], &[])
+        .expect("Unable to configure Cargo to work offline");
+    (config, cwd)
+}
+
 /// Finds the features specified by the custom metadata of `pkg`.
 ///
 /// Our custom metadata format looks like:
@@ -212,53 +576,116 @@ fn playground_metadata_features(pkg: &Package) -> Option<(Vec<String>, bool)> {
     }
 }
 
-pub fn generate_info(modifications: &Modifications) -> (BTreeMap<String, DependencySpec>, Vec<CrateInformation>) {
+pub fn generate_info(modifications: &Modifications, inputs: &Inputs) -> (BTreeMap<String, DependencySpec>, Vec<CrateInformation>) {
     // Setup to interact with cargo.
-    let config = Config::default().expect("Unable to create default Cargo config");
+    let (config, _config_dir) = match &inputs.local_registry {
+        Some(path) => {
+            let (config, config_dir) = local_registry_config(path);
+            (config, Some(config_dir))
+        }
+        None => (Config::default().expect("Unable to create default Cargo config"), None),
+    };
+
     let _lock = config.acquire_package_cache_lock();
     let crates_io = SourceId::crates_io(&config).expect("Unable to create crates.io source ID");
-    let mut source = RegistrySource::remote(crates_io, &HashSet::new(), &config);
-    source.update().expect("Unable to update registry");
+    // This honors source replacement, such as a local registry
+    let config_map = SourceConfigMap::new(&config).expect("Unable to read Cargo source configuration");
+    let mut sources = SourceMap::new();
+    load_source(&mut sources, &config_map, crates_io);
 
-    let mut top = TopCrates::download();
-    top.add_rust_cookbook_crates();
+    let mut top = TopCrates::load(inputs.top_crates.as_deref());
+    top.add_rust_cookbook_crates(inputs.cookbook_manifest.as_deref());
     top.add_curated_crates(modifications);
 
     // Find the newest (non-prerelease, non-yanked) versions of all
     // the interesting crates.
     let mut summaries = Vec::new();
-    for Crate { name } in &top.crates {
+    // The versions which get the crate's plain id
+    let mut main_versions = BTreeSet::new();
+    let mut requested_by: BTreeMap<&str, BTreeSet<CrateSource>> = BTreeMap::new();
+    for Crate {
+        name,
+        source: crate_source,
+    } in &top.crates
+    {
         if modifications.excluded(name) {
             continue;
         }
 
-        // Query the registry for a summary of this crate.
-        // Usefully, this doesn't seem to include yanked versions
-        let dep = Dependency::parse(name, None, crates_io)
-            .unwrap_or_else(|e| panic!("Unable to parse dependency for {}: {}", name, e));
+        // Crates can be requested more than once, such as a popular
+        // crate that the cookbook also uses
+        let crate_sources = requested_by.entry(name).or_default();
+        let already_requested = !crate_sources.is_empty();
+        crate_sources.insert(*crate_source);
+        if already_requested {
+            continue;
+        }
 
-        let matches = source.query_vec(&dep).unwrap_or_else(|e| {
-            panic!("Unable to query registry for {}: {}", name, e);
-        });
+        let source_id = modifications.source_id(name, crates_io);
+        load_source(&mut sources, &config_map, source_id);
+        let source = sources.get_mut(source_id).expect("Source was not loaded");
+        let forced_features = modifications
+            .crates
+            .get(name.as_str())
+            .map(|m| &*m.features)
+            .unwrap_or_default();
+        let mut selected_versions: Vec<semver::Version> = Vec::new();
+
+        for (i, requirement) in modifications.version_requirements(name).into_iter().enumerate() {
+            // Query the registry for a summary of this crate.
+            // Usefully, this doesn't seem to include yanked versions
+            let dep = Dependency::parse(name, requirement, source_id)
+                .unwrap_or_else(|e| panic!("Unable to parse dependency for {}: {}", name, e));
+
+            let matches = source.query_vec(&dep).unwrap_or_else(|e| {
+                panic!("Unable to query registry for {}: {}", name, e);
+            });
 
-        // Find the newest non-prelease version
-        let summary = matches
-            .into_iter()
-            .filter(|summary| !summary.version().is_prerelease())
-            .max_by_key(|summary| summary.version().clone())
-            .unwrap_or_else(|| panic!("Registry has no viable versions of {}", name));
-
-        // Add a dependency on this crate.
-        summaries.push((
-            summary,
-            ResolveOpts {
-                dev_deps: false,
-                features: RequestedFeatures::DepFeatures {
-                    features: Default::default(),
-                    uses_default_features: true,
-                },
-            },
-        ));
+            // Find the newest non-prelease version. Git repositories and
+            // paths only have one version, so take whatever it is.
+            let summary = matches
+                .into_iter()
+                .filter(|summary| !summary.version().is_prerelease() || !source_id.is_registry())
+                .max_by_key(|summary| summary.version().clone())
+                .unwrap_or_else(|| {
+                    panic!(
+                        "Registry has no viable versions of {} matching {}",
+                        name,
+                        requirement.unwrap_or("*")
+                    )
+                });
+
+            let version = summary.version();
+            let requirement = requirement.unwrap_or("*");
+
+            // Features of dependencies aren't listed by the crate itself
+            let missing_feature = forced_features
+                .iter()
+                .filter(|feature| !feature.contains('/'))
+                .find(|&feature| !summary.features().contains_key(feature.as_str()));
+            if let Some(feature) = missing_feature {
+                panic!(
+                    "The feature {} of {} does not exist in version {}, which matches {}",
+                    feature, name, version, requirement,
+                );
+            }
+
+            // Two compatible versions would get the same id
+            if let Some(other) = selected_versions.iter().find(|other| semver_compatible(other, version)) {
+                panic!(
+                    "Version {} of {}, which matches {}, is semver-compatible with version {} that is already included",
+                    version, name, requirement, other,
+                );
+            }
+            selected_versions.push(version.clone());
+
+            if i == 0 {
+                main_versions.insert(summary.package_id());
+            }
+
+            // Add a dependency on this crate.
+            summaries.push((summary, modifications.resolve_opts(name)));
+        }
     }
 
     // Resolve transitive dependencies.
@@ -307,8 +734,11 @@ pub fn generate_info(modifications: &Modifications) -> (BTreeMap<String, Depende
         .filter(|pkg| !modifications.excluded(pkg.name().as_str()))
         .collect();
 
-    let mut sources = SourceMap::new();
-    sources.insert(Box::new(source));
+    // Dependencies of crates outside of crates.io may come from
+    // anywhere
+    for package_id in &package_ids {
+        load_source(&mut sources, &config_map, package_id.source_id());
+    }
 
     let package_set =
         PackageSet::new(&package_ids, sources, &config).expect("Unable to create a PackageSet");
@@ -330,9 +760,18 @@ pub fn generate_info(modifications: &Modifications) -> (BTreeMap<String, Depende
     let mut infos = Vec::new();
 
     for (name, pkgs) in &packages.into_iter().group_by(|pkg| pkg.name()) {
-        let mut first = true;
-
-        for pkg in pkgs {
+        let pkgs: Vec<_> = pkgs.collect();
+
+        // The version that was requested directly keeps the plain
+        // name, even when something else depends on a newer
+        // version. Crates that are only dependencies use the newest.
+        let main = pkgs
+            .iter()
+            .position(|pkg| main_versions.contains(&pkg.package_id()))
+            .unwrap_or(0);
+
+        for (i, pkg) in pkgs.into_iter().enumerate() {
+            let is_main = i == main;
             let version = pkg.version();
 
             let crate_name = pkg
@@ -345,40 +784,203 @@ pub fn generate_info(modifications: &Modifications) -> (BTreeMap<String, Depende
                 .next()
                 .unwrap_or_else(|| panic!("{} did not have a library", name));
 
-            // We see the newest version first. Any subsequent
-            // versions will have their version appended so that they
-            // are uniquely named
-            let exposed_name = if first {
+            // Any other versions will have their semver-compatible
+            // version appended so that they are uniquely named without
+            // the name changing on every release. Cargo doesn't allow
+            // two compatible versions of a crate, so this is unique.
+            let exposed_name = if is_main {
                 crate_name.clone()
             } else {
-                format!(
-                    "{}_{}_{}_{}",
-                    crate_name, version.major, version.minor, version.patch
-                )
+                let compatible = if version.major != 0 {
+                    version.major.to_string()
+                } else if version.minor != 0 {
+                    format!("0_{}", version.minor)
+                } else {
+                    format!("0_0_{}", version.patch)
+                };
+
+                format!("{}_{}", crate_name, compatible)
             };
 
-            let (features, default_features) =
+            let (mut features, mut default_features) =
                 playground_metadata_features(&pkg).unwrap_or_else(|| (Vec::new(), true));
+            modifications.override_features(name.as_str(), &mut features, &mut default_features);
 
-            dependencies.insert(
-                exposed_name.clone(),
-                DependencySpec {
-                    package: name.to_string(),
-                    version: version.to_string(),
-                    features,
-                    default_features,
-                },
-            );
+            let mut spec = DependencySpec {
+                package: name.to_string(),
+                version: version.to_string(),
+                features,
+                default_features,
+                registry: None,
+                git: None,
+                branch: None,
+                tag: None,
+                rev: None,
+                path: None,
+            };
+            modifications.set_location(&mut spec, pkg.package_id().source_id());
+
+            dependencies.insert(exposed_name.clone(), spec);
+
+            let metadata = pkg.manifest().metadata();
+            let available_features = pkg
+                .summary()
+                .features()
+                .keys()
+                .map(ToString::to_string)
+                .collect();
+            // Only the main version was requested directly
+            let sources = if is_main {
+                requested_by.get(name.as_str()).cloned().unwrap_or_default()
+            } else {
+                BTreeSet::new()
+            };
 
             infos.push(CrateInformation {
                 name: name.to_string(),
                 version: version.to_string(),
                 id: exposed_name,
+                description: metadata.description.clone(),
+                documentation: metadata.documentation.clone(),
+                repository: metadata.repository.clone(),
+                license: metadata.license.clone(),
+                features: resolve
+                    .features(pkg.package_id())
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect(),
+                available_features,
+                sources,
+                aliases: Vec::new(),
             });
-
-            first = false;
         }
     }
 
     (dependencies, infos)
 }
+
+impl Aliases {
+    /// Finds the id that each alias refers to, recording the aliases
+    /// alongside the crates. Aliases for versions that are no longer
+    /// available, or that would hide a real id, are left out.
+    pub fn resolve(&self, infos: &mut [CrateInformation]) -> BTreeMap<String, String> {
+        let ids: BTreeSet<_> = infos.iter().map(|info| info.id.clone()).collect();
+        let mut resolved = BTreeMap::new();
+
+        for (alias, target) in &self.0 {
+            if ids.contains(alias) {
+                continue;
+            }
+
+            let requirement = semver::VersionReq::parse(&target.version).unwrap_or_else(|e| {
+                panic!("The alias {} has an invalid version requirement: {}", alias, e)
+            });
+
+            let info = infos.iter_mut().find(|info| {
+                info.name == target.package
+                    && matches!(semver::Version::parse(&info.version), Ok(v) if requirement.matches(&v))
+            });
+
+            if let Some(info) = info {
+                info.aliases.push(alias.clone());
+                resolved.insert(alias.clone(), info.id.clone());
+            }
+        }
+
+        resolved
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn semver_compatibility() {
+        let v = |s| semver::Version::parse(s).unwrap();
+
+        assert!(semver_compatible(&v("1.2.0"), &v("1.9.3")));
+        assert!(!semver_compatible(&v("1.2.0"), &v("2.0.0")));
+        assert!(semver_compatible(&v("0.7.1"), &v("0.7.3")));
+        assert!(!semver_compatible(&v("0.7.3"), &v("0.8.0")));
+        assert!(!semver_compatible(&v("0.0.1"), &v("0.0.2")));
+    }
+
+    fn info(name: &str, version: &str, id: &str) -> CrateInformation {
+        CrateInformation {
+            name: name.into(),
+            version: version.into(),
+            id: id.into(),
+            description: None,
+            documentation: None,
+            repository: None,
+            license: None,
+            features: Vec::new(),
+            available_features: Vec::new(),
+            sources: BTreeSet::new(),
+            aliases: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn aliases_follow_compatible_versions() {
+        let aliases: Aliases = toml::from_str(
+            r#"
+            rand_0_7_3 = { package = "rand", version = "0.7" }
+            itoa_0_4_8 = { package = "itoa", version = "0.4" }
+            spin_0_5_2 = { package = "spin", version = "0.5" }
+            "#,
+        )
+        .unwrap();
+
+        let mut infos = vec![
+            info("rand", "0.8.5", "rand"),
+            info("rand", "0.7.4", "rand_0_7"),
+            info("itoa", "0.4.9", "itoa"),
+            info("spin", "0.9.2", "spin"),
+        ];
+
+        let resolved = aliases.resolve(&mut infos);
+
+        assert_eq!(resolved["rand_0_7_3"], "rand_0_7");
+        assert_eq!(resolved["itoa_0_4_8"], "itoa");
+        assert!(!resolved.contains_key("spin_0_5_2"));
+        assert_eq!(infos[1].aliases, ["rand_0_7_3"]);
+        assert!(infos[0].aliases.is_empty());
+    }
+
+    #[test]
+    fn local_registry_replaces_crates_io() {
+        let registry = tempfile::tempdir().unwrap();
+        let index = registry.path().join("index").join("ra").join("nd");
+        std::fs::create_dir_all(&index).unwrap();
+        std::fs::write(
+            index.join("rand"),
+            format!(
+                r#"{{"name":"rand","vers":"0.8.5","deps":[],"cksum":"{}","features":{{}},"yanked":false}}"#,
+                "0".repeat(64),
+            ),
+        )
+        .unwrap();
+
+        let (config, _config_dir) = local_registry_config(registry.path());
+
+        let _lock = config.acquire_package_cache_lock().unwrap();
+        let crates_io = SourceId::crates_io(&config).unwrap();
+        let config_map = SourceConfigMap::new(&config).unwrap();
+        let mut sources = SourceMap::new();
+        load_source(&mut sources, &config_map, crates_io);
+
+        let dep = Dependency::parse("rand", None, crates_io).unwrap();
+        let versions: Vec<_> = sources
+            .get_mut(crates_io)
+            .unwrap()
+            .query_vec(&dep)
+            .unwrap()
+            .iter()
+            .map(|summary| summary.version().to_string())
+            .collect();
+
+        assert_eq!(versions, ["0.8.5"]);
+    }
+}
@@ -4,11 +4,16 @@ use rust_playground_top_crates::*;
 use serde::Serialize;
 use std::{
     collections::BTreeMap,
-    fs::File,
+    fs::{self, File},
     io::{Read, Write},
     path::{Path, PathBuf},
 };
 
+/// Where crates from `path` sources are copied to, relative to the
+/// Docker build context, and where that ends up in the image
+const PATH_SOURCES_DIR: &str = "path-sources";
+const PATH_SOURCES_IN_IMAGE: &str = "/playground/path-sources";
+
 /// A Cargo.toml file.
 #[derive(Serialize)]
 struct TomlManifest {
@@ -25,6 +30,19 @@ struct TomlPackage {
     version: String,
     authors: Vec<String>,
     resolver: String,
+    metadata: PackageMetadata,
+}
+
+/// Settings for the tools inside the container, which Cargo ignores
+#[derive(Serialize)]
+struct PackageMetadata {
+    playground: PlaygroundMetadata,
+}
+
+#[derive(Serialize)]
+struct PlaygroundMetadata {
+    /// Older ids and the id of the crate that each still refers to
+    aliases: BTreeMap<String, String>,
 }
 
 /// A profile section in a Cargo.toml file
@@ -42,6 +60,18 @@ struct Profiles {
     release: Profile,
 }
 
+/// A Cargo configuration file, naming the registries that the
+/// dependencies in Cargo.toml refer to
+#[derive(Serialize)]
+struct CargoConfig {
+    registries: BTreeMap<String, Registry>,
+}
+
+#[derive(Serialize)]
+struct Registry {
+    index: String,
+}
+
 fn main() {
     let mut f = File::open("crate-modifications.toml")
         .expect("unable to open crate modifications file");
@@ -52,7 +82,56 @@ fn main() {
 
     let modifications: Modifications = toml::from_slice(&d).expect("unable to parse crate modifications file");
 
-    let (dependencies, infos) = rust_playground_top_crates::generate_info(&modifications);
+    // Setting these allows generating the crate list without internet
+    // access, such as from a mirror
+    let inputs = Inputs {
+        top_crates: std::env::var_os("PLAYGROUND_TOP_CRATES").map(Into::into),
+        cookbook_manifest: std::env::var_os("PLAYGROUND_COOKBOOK_MANIFEST").map(Into::into),
+        local_registry: std::env::var_os("PLAYGROUND_LOCAL_REGISTRY").map(Into::into),
+    };
+
+    let aliases = std::fs::read_to_string("crate-aliases.toml")
+        .expect("unable to read crate aliases file");
+    let aliases: Aliases = toml::from_str(&aliases).expect("unable to parse crate aliases file");
+
+    let (mut dependencies, mut infos) = rust_playground_top_crates::generate_info(&modifications, &inputs);
+    let aliases = aliases.resolve(&mut infos);
+
+    // Check that everything builds before replacing the existing files
+    if let Some(report_path) = std::env::var_os("PLAYGROUND_VERIFY_REPORT") {
+        let report_path = PathBuf::from(report_path);
+        let work_dir = tempfile::Builder::new()
+            .prefix("playground-top-crates")
+            .tempdir()
+            .expect("Unable to create a directory to verify the crates in");
+        let report = verify::verify(&dependencies, &modifications, &inputs, work_dir.path());
+
+        let mut f = File::create(&report_path)
+            .unwrap_or_else(|e| panic!("Unable to create {}: {}", report_path.display(), e));
+        serde_json::to_writer_pretty(&mut f, &report)
+            .unwrap_or_else(|e| panic!("Unable to write {}: {}", report_path.display(), e));
+        println!("Wrote {}", report_path.display());
+
+        if !report.success {
+            eprintln!("Some crates do not build; nothing else was written");
+            std::process::exit(1);
+        }
+    }
+
+    let base_directory: PathBuf = std::env::args_os()
+        .nth(1)
+        .unwrap_or_else(|| "../compiler/base".into())
+        .into();
+
+    copy_path_sources(&mut dependencies, &base_directory);
+
+    let cargo_config = CargoConfig {
+        registries: modifications
+            .registries
+            .iter()
+            .map(|(name, index)| (name.clone(), Registry { index: index.clone() }))
+            .collect(),
+    };
 
     // Construct playground's Cargo.toml.
     let manifest = TomlManifest {
@@ -61,6 +140,9 @@ fn main() {
             version: "0.0.1".to_owned(),
             authors: vec!["The Rust Playground".to_owned()],
             resolver: "2".to_owned(),
+            metadata: PackageMetadata {
+                playground: PlaygroundMetadata { aliases },
+            },
         },
         profile: Profiles {
             dev: Profile {
@@ -76,15 +158,29 @@ fn main() {
     };
 
     // Write manifest file.
-    let base_directory: PathBuf = std::env::args_os()
-        .nth(1)
-        .unwrap_or_else(|| "../compiler/base".into())
-        .into();
-
     let cargo_toml = base_directory.join("Cargo.toml");
+
+    // Describe the changes before they are overwritten
+    if let Some(report_path) = std::env::var_os("PLAYGROUND_TOP_CRATES_REPORT") {
+        let report_path = PathBuf::from(report_path);
+        let old_manifest = std::fs::read_to_string(&cargo_toml).unwrap_or_default();
+        let old_information =
+            std::fs::read_to_string(base_directory.join("crate-information.json")).unwrap_or_default();
+        let report = report::generate(&old_manifest, &old_information, &manifest.dependencies, &infos);
+        std::fs::write(&report_path, report)
+            .unwrap_or_else(|e| panic!("Unable to write {}: {}", report_path.display(), e));
+        println!("Wrote {}", report_path.display());
+    }
+
     write_manifest(manifest, &cargo_toml);
     println!("wrote {}", cargo_toml.display());
 
+    let path = base_directory.join("cargo-config.toml");
+    let content = toml::to_vec(&cargo_config).expect("Couldn't serialize TOML");
+    std::fs::write(&path, content)
+        .unwrap_or_else(|e| panic!("Unable to write {}: {}", path.display(), e));
+    println!("Wrote {}", path.display());
+
     let path = base_directory.join("crate-information.json");
     let mut f = File::create(&path)
         .unwrap_or_else(|e| panic!("Unable to create {}: {}", path.display(), e));
@@ -98,3 +194,55 @@ fn write_manifest(manifest: TomlManifest, path: impl AsRef<Path>) {
     let content = toml::to_vec(&manifest).expect("Couldn't serialize TOML");
     f.write_all(&content).expect("Couldn't write Cargo.toml");
 }
+
+/// Copies crates from `path` sources into the Docker build context
+/// and points their dependencies at the copy, as paths on this machine
+/// don't exist when the image is built.
+fn copy_path_sources(dependencies: &mut BTreeMap<String, DependencySpec>, base_directory: &Path) {
+    let dir = base_directory.join(PATH_SOURCES_DIR);
+
+    // Remove the copies from previous runs, leaving the README
+    if let Ok(entries) = fs::read_dir(&dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                fs::remove_dir_all(&path)
+                    .unwrap_or_else(|e| panic!("Unable to remove {}: {}", path.display(), e));
+            }
+        }
+    }
+
+    for (id, spec) in dependencies {
+        let path = match spec.path.take() {
+            Some(path) => path,
+            None => continue,
+        };
+
+        copy_dir(Path::new(&path), &dir.join(id));
+        spec.path = Some(format!("{}/{}", PATH_SOURCES_IN_IMAGE, id));
+        println!("Copied {} to {}", path, dir.join(id).display());
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    fs::create_dir_all(to).unwrap_or_else(|e| panic!("Unable to create {}: {}", to.display(), e));
+
+    let entries = fs::read_dir(from).unwrap_or_else(|e| panic!("Unable to read {}: {}", from.display(), e));
+    for entry in entries {
+        let entry = entry.unwrap_or_else(|e| panic!("Unable to read {}: {}", from.display(), e));
+        let name = entry.file_name();
+
+        // Build output and history aren't needed to build the crate
+        if name == "target" || name == ".git" {
+            continue;
+        }
+
+        let (source, destination) = (entry.path(), to.join(&name));
+        if source.is_dir() {
+            copy_dir(&source, &destination);
+        } else {
+            fs::copy(&source, &destination)
+                .unwrap_or_else(|e| panic!("Unable to copy {}: {}", source.display(), e));
+        }
+    }
+}
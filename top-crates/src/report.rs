@@ -0,0 +1,524 @@
+//! Describes how the generated crate list changed, so that reviewers
+//! don't need to read the diff of the entire Cargo.toml.
+
+use crate::{CrateInformation, CrateSource, DependencySpec};
+use semver::Version;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+/// The parts of a dependency that are worth reporting on
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    name: String,
+    version: Version,
+    features: BTreeSet<String>,
+    default_features: bool,
+}
+
+/// Every version of each package, newest first
+type Packages = BTreeMap<String, Vec<Entry>>;
+
+/// The parts of crate-information.json that are worth reporting on
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+struct Information {
+    /// Missing from files written before sources were recorded
+    sources: Option<BTreeSet<CrateSource>>,
+    #[serde(default)]
+    aliases: Vec<String>,
+}
+
+/// Crate information, keyed by id
+type InformationById = BTreeMap<String, Information>;
+
+/// A titled list of changes
+type Section = (&'static str, Vec<String>);
+
+fn parse_version(version: &str) -> Version {
+    let version = version.trim_start_matches('=');
+    Version::parse(version).unwrap_or_else(|e| panic!("Invalid version {}: {}", version, e))
+}
+
+fn from_specs(dependencies: &BTreeMap<String, DependencySpec>) -> Packages {
+    let entries = dependencies.iter().map(|(name, spec)| {
+        let package = if spec.package.is_empty() {
+            name
+        } else {
+            &spec.package
+        };
+
+        let entry = Entry {
+            name: name.clone(),
+            version: parse_version(&spec.version),
+            features: spec.features.iter().cloned().collect(),
+            default_features: spec.default_features,
+        };
+
+        (package.clone(), entry)
+    });
+
+    group(entries)
+}
+
+fn from_manifest(manifest: &str) -> Packages {
+    let manifest: toml::Value = manifest
+        .parse()
+        .unwrap_or_else(|e| panic!("Unable to parse the existing Cargo.toml: {}", e));
+
+    let dependencies = match manifest.get("dependencies").and_then(|d| d.as_table()) {
+        Some(dependencies) => dependencies,
+        None => return Packages::new(),
+    };
+
+    let entries = dependencies.iter().map(|(name, value)| {
+        let field = |key| value.get(key).and_then(toml::Value::as_str);
+
+        let package = field("package").unwrap_or(name);
+        let version = value.as_str().or_else(|| field("version")).unwrap_or("0.0.0");
+        let features = value
+            .get("features")
+            .and_then(toml::Value::as_array)
+            .map(|f| f.iter().filter_map(|f| f.as_str()).map(Into::into).collect())
+            .unwrap_or_default();
+        let default_features = value
+            .get("default-features")
+            .and_then(toml::Value::as_bool)
+            .unwrap_or(true);
+
+        let entry = Entry {
+            name: name.clone(),
+            version: parse_version(version),
+            features,
+            default_features,
+        };
+
+        (package.to_string(), entry)
+    });
+
+    group(entries)
+}
+
+fn from_information(infos: &[CrateInformation]) -> InformationById {
+    infos
+        .iter()
+        .map(|info| {
+            let information = Information {
+                sources: Some(info.sources.clone()),
+                aliases: info.aliases.clone(),
+            };
+            (info.id.clone(), information)
+        })
+        .collect()
+}
+
+fn from_json(json: &str) -> InformationById {
+    if json.trim().is_empty() {
+        return InformationById::new();
+    }
+
+    #[derive(Deserialize)]
+    struct Keyed {
+        id: String,
+        #[serde(flatten)]
+        information: Information,
+    }
+
+    let infos: Vec<Keyed> = serde_json::from_str(json)
+        .unwrap_or_else(|e| panic!("Unable to parse the existing crate information: {}", e));
+    infos.into_iter().map(|i| (i.id, i.information)).collect()
+}
+
+fn group(entries: impl Iterator<Item = (String, Entry)>) -> Packages {
+    let mut packages = Packages::new();
+    for (package, entry) in entries {
+        packages.entry(package).or_default().push(entry);
+    }
+    for entries in packages.values_mut() {
+        entries.sort_by(|a, b| b.version.cmp(&a.version));
+    }
+    packages
+}
+
+// Cargo considers versions compatible when their leftmost non-zero
+// components are the same
+fn is_breaking(old: &Version, new: &Version) -> bool {
+    if old.major != new.major {
+        true
+    } else if old.major != 0 {
+        false
+    } else if old.minor != new.minor {
+        true
+    } else {
+        old.minor == 0 && old.patch != new.patch
+    }
+}
+
+fn list(items: &BTreeSet<String>) -> String {
+    items
+        .iter()
+        .map(|i| format!("`{}`", i))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn ids(entries: &[Entry]) -> BTreeSet<String> {
+    entries.iter().map(|e| e.name.clone()).collect()
+}
+
+fn source_name(source: CrateSource) -> &'static str {
+    match source {
+        CrateSource::TopDownloads => "top-downloads",
+        CrateSource::Cookbook => "cookbook",
+        CrateSource::Addition => "addition",
+    }
+}
+
+fn sources(sources: &BTreeSet<CrateSource>) -> String {
+    list(&sources.iter().map(|s| source_name(*s).to_string()).collect())
+}
+
+/// Compares the dependencies of an existing Cargo.toml and
+/// crate-information.json with newly generated ones, returning a
+/// Markdown summary.
+pub fn generate(
+    old_manifest: &str,
+    old_information: &str,
+    new: &BTreeMap<String, DependencySpec>,
+    new_information: &[CrateInformation],
+) -> String {
+    let mut sections = compare_packages(&from_manifest(old_manifest), &from_specs(new));
+    sections.extend(compare_information(
+        &from_json(old_information),
+        &from_information(new_information),
+    ));
+    render(&sections)
+}
+
+fn compare_packages(old: &Packages, new: &Packages) -> Vec<Section> {
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    let mut bumped = Vec::new();
+    let mut features = Vec::new();
+    let mut new_ids = Vec::new();
+    let mut removed_ids = Vec::new();
+
+    for (package, new_entries) in new {
+        let old_entries = match old.get(package) {
+            Some(old_entries) => old_entries,
+            None => {
+                added.push(format!("`{}` {}", package, new_entries[0].version));
+                if new_entries.len() > 1 {
+                    new_ids.push(format!("`{}`: {}", package, list(&ids(new_entries))));
+                }
+                continue;
+            }
+        };
+
+        let (old_newest, new_newest) = (&old_entries[0], &new_entries[0]);
+
+        if old_newest.version != new_newest.version {
+            let breaking = if is_breaking(&old_newest.version, &new_newest.version) {
+                " **(breaking)**"
+            } else {
+                ""
+            };
+            bumped.push(format!(
+                "`{}` {} → {}{}",
+                package, old_newest.version, new_newest.version, breaking,
+            ));
+        }
+
+        let enabled = &new_newest.features - &old_newest.features;
+        let disabled = &old_newest.features - &new_newest.features;
+        let mut changes = Vec::new();
+        if !enabled.is_empty() {
+            changes.push(format!("enabled {}", list(&enabled)));
+        }
+        if !disabled.is_empty() {
+            changes.push(format!("disabled {}", list(&disabled)));
+        }
+        if old_newest.default_features != new_newest.default_features {
+            let state = if new_newest.default_features {
+                "enabled"
+            } else {
+                "disabled"
+            };
+            changes.push(format!("{} default features", state));
+        }
+        if !changes.is_empty() {
+            features.push(format!("`{}`: {}", package, changes.join("; ")));
+        }
+
+        // Any id that code can no longer use is worth knowing about,
+        // as is every new one
+        let (old_ids, current_ids) = (ids(old_entries), ids(new_entries));
+        let exposed = &current_ids - &old_ids;
+        if !exposed.is_empty() {
+            new_ids.push(format!("`{}`: {}", package, list(&exposed)));
+        }
+        let gone = &old_ids - &current_ids;
+        if !gone.is_empty() {
+            removed_ids.push(format!("`{}`: {}", package, list(&gone)));
+        }
+    }
+
+    for (package, old_entries) in old {
+        if !new.contains_key(package) {
+            removed.push(format!("`{}` {}", package, old_entries[0].version));
+        }
+    }
+
+    vec![
+        ("Added", added),
+        ("Removed", removed),
+        ("Version changes", bumped),
+        ("Feature changes", features),
+        ("New ids", new_ids),
+        ("Removed ids", removed_ids),
+    ]
+}
+
+fn compare_information(old: &InformationById, new: &InformationById) -> Vec<Section> {
+    let mut requested = Vec::new();
+    let mut aliases = Vec::new();
+
+    for (id, new_info) in new {
+        let old_info = match old.get(id) {
+            Some(old_info) => old_info,
+            None => continue,
+        };
+
+        let (old_sources, new_sources) = match (&old_info.sources, &new_info.sources) {
+            (Some(old_sources), Some(new_sources)) => (old_sources, new_sources),
+            _ => continue,
+        };
+
+        let now = new_sources - old_sources;
+        let no_longer = old_sources - new_sources;
+        let mut changes = Vec::new();
+        if !now.is_empty() {
+            changes.push(format!("now from {}", sources(&now)));
+        }
+        if !no_longer.is_empty() {
+            changes.push(format!("no longer from {}", sources(&no_longer)));
+        }
+        if !changes.is_empty() {
+            requested.push(format!("`{}`: {}", id, changes.join("; ")));
+        }
+    }
+
+    let alias_targets = |infos: &InformationById| -> BTreeMap<String, String> {
+        infos
+            .iter()
+            .flat_map(|(id, info)| info.aliases.iter().map(move |a| (a.clone(), id.clone())))
+            .collect()
+    };
+    let (old_aliases, new_aliases) = (alias_targets(old), alias_targets(new));
+
+    for (alias, id) in &new_aliases {
+        if old_aliases.get(alias) != Some(id) {
+            aliases.push(format!("`{}` → `{}`", alias, id));
+        }
+    }
+    for alias in old_aliases.keys() {
+        if !new_aliases.contains_key(alias) {
+            aliases.push(format!("`{}` removed", alias));
+        }
+    }
+
+    vec![("Source changes", requested), ("Alias changes", aliases)]
+}
+
+fn render(sections: &[Section]) -> String {
+    let mut report = String::from("# Crate changes\n");
+    let mut unchanged = true;
+
+    for (title, items) in sections {
+        if items.is_empty() {
+            continue;
+        }
+        unchanged = false;
+
+        let _ = write!(report, "\n## {}\n\n", title);
+        for item in items {
+            let _ = writeln!(report, "- {}", item);
+        }
+    }
+
+    if unchanged {
+        report.push_str("\nNo changes\n");
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn breaking_versions() {
+        let cases = [
+            ("1.2.3", "1.2.4", false),
+            ("1.2.3", "1.9.0", false),
+            ("1.2.3", "2.0.0", true),
+            ("0.7.3", "0.7.4", false),
+            ("0.7.3", "0.8.0", true),
+            ("0.0.1", "0.0.1", false),
+            ("0.0.1", "0.0.2", true),
+            ("0.9.0", "1.0.0", true),
+        ];
+
+        for &(old, new, breaking) in &cases {
+            assert_eq!(
+                is_breaking(&parse_version(old), &parse_version(new)),
+                breaking,
+                "{} → {}",
+                old,
+                new,
+            );
+        }
+    }
+
+    #[test]
+    fn package_changes() {
+        let cases = [
+            (
+                r#"a = "=1.0.0""#,
+                r#"a = "=1.0.0""#,
+                vec![],
+            ),
+            (
+                r#"a = "=1.0.0""#,
+                r#"a = "=1.0.0"
+                   b = "=0.1.0""#,
+                vec![("Added", "`b` 0.1.0")],
+            ),
+            (
+                r#"a = "=1.0.0"
+                   b = "=0.1.0""#,
+                r#"a = "=1.0.0""#,
+                vec![("Removed", "`b` 0.1.0")],
+            ),
+            (
+                r#"a = "=1.0.0""#,
+                r#"a = "=2.0.0""#,
+                vec![("Version changes", "`a` 1.0.0 → 2.0.0 **(breaking)**")],
+            ),
+            (
+                r#"a = "=1.0.0""#,
+                r#"a = "=1.1.0""#,
+                vec![("Version changes", "`a` 1.0.0 → 1.1.0")],
+            ),
+            (
+                r#"a = { version = "=1.0.0", features = ["x"] }"#,
+                r#"a = { version = "=1.0.0", features = ["y"], default-features = false }"#,
+                vec![("Feature changes", "`a`: enabled `y`; disabled `x`; disabled default features")],
+            ),
+            (
+                r#"rand = "=0.8.5"
+                   rand_0_7 = { package = "rand", version = "=0.7.3" }"#,
+                r#"rand = "=0.8.5"
+                   rand_0_7 = { package = "rand", version = "=0.7.3" }
+                   rand_0_6 = { package = "rand", version = "=0.6.5" }"#,
+                vec![("New ids", "`rand`: `rand_0_6`")],
+            ),
+            (
+                r#"rand = "=0.8.5"
+                   rand_0_7 = { package = "rand", version = "=0.7.3" }"#,
+                r#"rand = "=0.8.5""#,
+                vec![("Removed ids", "`rand`: `rand_0_7`")],
+            ),
+            (
+                "",
+                r#"rand = "=0.8.5"
+                   rand_0_7 = { package = "rand", version = "=0.7.3" }"#,
+                vec![("Added", "`rand` 0.8.5"), ("New ids", "`rand`: `rand`, `rand_0_7`")],
+            ),
+        ];
+
+        for (old, new, expected) in &cases {
+            let manifest = |deps| format!("[dependencies]\n{}", deps);
+            let sections = compare_packages(&from_manifest(&manifest(old)), &from_manifest(&manifest(new)));
+
+            let changes: Vec<_> = sections
+                .iter()
+                .flat_map(|(title, items)| items.iter().map(move |item| (*title, item.as_str())))
+                .collect();
+            assert_eq!(&changes, expected, "{} => {}", old, new);
+        }
+    }
+
+    #[test]
+    fn information_changes() {
+        let cases = [
+            (
+                r#"[{ "id": "a", "sources": ["top-downloads"] }]"#,
+                r#"[{ "id": "a", "sources": ["top-downloads"] }]"#,
+                vec![],
+            ),
+            (
+                r#"[{ "id": "a", "sources": ["top-downloads"] }]"#,
+                r#"[{ "id": "a", "sources": ["cookbook"] }]"#,
+                vec![("Source changes", "`a`: now from `cookbook`; no longer from `top-downloads`")],
+            ),
+            (
+                r#"[{ "id": "a" }]"#,
+                r#"[{ "id": "a", "sources": ["cookbook"] }]"#,
+                vec![],
+            ),
+            (
+                r#"[{ "id": "rand_0_7" }]"#,
+                r#"[{ "id": "rand_0_7", "aliases": ["rand_0_7_3"] }]"#,
+                vec![("Alias changes", "`rand_0_7_3` → `rand_0_7`")],
+            ),
+            (
+                r#"[{ "id": "rand_0_7", "aliases": ["rand_0_7_3"] }]"#,
+                r#"[{ "id": "rand", "aliases": ["rand_0_7_3"] }]"#,
+                vec![("Alias changes", "`rand_0_7_3` → `rand`")],
+            ),
+            (
+                r#"[{ "id": "rand_0_7", "aliases": ["rand_0_7_3"] }]"#,
+                r#"[{ "id": "rand_0_7" }]"#,
+                vec![("Alias changes", "`rand_0_7_3` removed")],
+            ),
+        ];
+
+        for (old, new, expected) in &cases {
+            let sections = compare_information(&from_json(old), &from_json(new));
+
+            let changes: Vec<_> = sections
+                .iter()
+                .flat_map(|(title, items)| items.iter().map(move |item| (*title, item.as_str())))
+                .collect();
+            assert_eq!(&changes, expected, "{} => {}", old, new);
+        }
+    }
+
+    #[test]
+    fn sections_rendered_in_order() {
+        let sections = [
+            ("Added", vec!["`b` 0.1.0".to_string()]),
+            ("Removed", vec![]),
+            ("Version changes", vec!["`a` 1.0.0 → 1.1.0".to_string(), "`c` 0.1.0 → 0.2.0 **(breaking)**".to_string()]),
+        ];
+
+        assert_eq!(
+            render(&sections),
+            "# Crate changes\n\
+             \n## Added\n\n\
+             - `b` 0.1.0\n\
+             \n## Version changes\n\n\
+             - `a` 1.0.0 → 1.1.0\n\
+             - `c` 0.1.0 → 0.2.0 **(breaking)**\n",
+        );
+        assert_eq!(render(&[("Added", vec![])]), "# Crate changes\n\nNo changes\n");
+    }
+
+    #[test]
+    fn empty_inputs_have_no_changes() {
+        assert_eq!(generate("", "", &BTreeMap::new(), &[]), "# Crate changes\n\nNo changes\n");
+    }
+}
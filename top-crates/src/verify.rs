@@ -0,0 +1,338 @@
+//! Checks that the generated crates build before they are published,
+//! finding the ones that don't.
+
+use crate::{DependencySpec, Inputs, Modifications, PLAYGROUND_TARGET_PLATFORM};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// The outcome of verification, suitable for feeding back into
+/// `crate-modifications.toml`
+#[derive(Debug, Default, Serialize)]
+pub struct VerificationReport {
+    pub success: bool,
+    /// Crates that fail to build even on their own
+    pub broken: Vec<BrokenCrate>,
+    /// Groups of crates that each build on their own but fail when
+    /// built together, such as from conflicting features
+    pub conflicts: Vec<Vec<String>>,
+    /// The package names of the broken crates, ready to be added to
+    /// `exclusions`
+    pub exclusions: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BrokenCrate {
+    pub id: String,
+    pub package: String,
+    pub version: String,
+    pub stderr: String,
+}
+
+#[derive(Serialize)]
+struct Manifest<'a> {
+    package: Package,
+    #[serde(serialize_with = "toml::ser::tables_last")]
+    dependencies: BTreeMap<&'a str, &'a DependencySpec>,
+}
+
+#[derive(Serialize)]
+struct Package {
+    name: String,
+    version: String,
+    resolver: String,
+}
+
+#[derive(Serialize)]
+struct CargoConfig<'a> {
+    registries: BTreeMap<&'a str, Registry<'a>>,
+}
+
+#[derive(Serialize)]
+struct Registry<'a> {
+    index: &'a str,
+}
+
+/// A project for running `cargo check` on subsets of the crates
+struct Project<'a> {
+    dependencies: &'a BTreeMap<String, DependencySpec>,
+    path: PathBuf,
+}
+
+impl Project<'_> {
+    /// Runs `cargo check` on a project that depends on and imports
+    /// only the crates in `ids`, returning the errors on failure
+    fn check(&self, ids: &[&str]) -> Result<(), String> {
+        let manifest = Manifest {
+            package: Package {
+                name: "playground-verify".to_owned(),
+                version: "0.0.0".to_owned(),
+                resolver: "2".to_owned(),
+            },
+            dependencies: ids
+                .iter()
+                .map(|&id| (id, &self.dependencies[id]))
+                .collect(),
+        };
+        let manifest = toml::to_string(&manifest).expect("Couldn't serialize TOML");
+        write(&self.path.join("Cargo.toml"), &manifest);
+
+        let lib: String = ids
+            .iter()
+            .map(|id| format!("extern crate {};\n", id))
+            .collect();
+        write(&self.path.join("src/lib.rs"), &lib);
+
+        // Subsets may resolve differently from the whole set
+        let _ = fs::remove_file(self.path.join("Cargo.lock"));
+
+        let output = Command::new("cargo")
+            .arg("check")
+            .arg("--quiet")
+            .args(["--target", PLAYGROUND_TARGET_PLATFORM])
+            .current_dir(&self.path)
+            .output()
+            .expect("Unable to run cargo");
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).into_owned())
+        }
+    }
+}
+
+struct Verifier<'a, C> {
+    dependencies: &'a BTreeMap<String, DependencySpec>,
+    /// Builds the given crates together, returning the errors on
+    /// failure
+    check: C,
+}
+
+impl<C> Verifier<'_, C>
+where
+    C: Fn(&[&str]) -> Result<(), String>,
+{
+    fn run(&self, ids: &[&str]) -> VerificationReport {
+        let mut report = VerificationReport::default();
+
+        match (self.check)(ids) {
+            Ok(()) => report.success = true,
+            Err(stderr) => self.bisect(ids, stderr, &mut report),
+        }
+
+        report.exclusions = report.broken.iter().map(|b| b.package.clone()).collect();
+        report.exclusions.sort();
+        report.exclusions.dedup();
+
+        report
+    }
+
+    /// Splits `ids` in half until the failures are isolated
+    fn bisect(&self, ids: &[&str], stderr: String, report: &mut VerificationReport) {
+        if let [id] = ids {
+            let spec = &self.dependencies[*id];
+            let package = if spec.package.is_empty() {
+                id.to_string()
+            } else {
+                spec.package.clone()
+            };
+
+            eprintln!("{} does not build", id);
+            report.broken.push(BrokenCrate {
+                id: id.to_string(),
+                package,
+                version: spec.version.clone(),
+                stderr,
+            });
+            return;
+        }
+
+        let (left, right) = ids.split_at(ids.len() / 2);
+        let left_result = (self.check)(left);
+        let right_result = (self.check)(right);
+
+        if left_result.is_ok() && right_result.is_ok() {
+            let conflict = self.narrow(&[], ids);
+            eprintln!("{} only fail when built together", conflict.join(", "));
+
+            // Something else may still fail once the conflict is
+            // out of the way
+            let rest: Vec<_> = ids.iter().copied().filter(|id| !conflict.contains(id)).collect();
+            report
+                .conflicts
+                .push(conflict.iter().map(ToString::to_string).collect());
+
+            if let Err(stderr) = (self.check)(&rest) {
+                self.bisect(&rest, stderr, report);
+            }
+            return;
+        }
+
+        if let Err(stderr) = left_result {
+            self.bisect(left, stderr, report);
+        }
+        if let Err(stderr) = right_result {
+            self.bisect(right, stderr, report);
+        }
+    }
+
+    /// Finds a small subset of `candidates` which fails to build
+    /// alongside `fixed`, when `fixed` builds on its own but not with
+    /// all of `candidates`. When neither half of `candidates` is
+    /// enough, each half is narrowed while holding the other fixed.
+    fn narrow<'b>(&self, fixed: &[&'b str], candidates: &[&'b str]) -> Vec<&'b str> {
+        if candidates.len() <= 1 {
+            return candidates.to_vec();
+        }
+
+        let with_fixed = |ids: &[&'b str]| -> Vec<&'b str> { fixed.iter().chain(ids).copied().collect() };
+
+        let (left, right) = candidates.split_at(candidates.len() / 2);
+        if (self.check)(&with_fixed(left)).is_err() {
+            return self.narrow(fixed, left);
+        }
+        if (self.check)(&with_fixed(right)).is_err() {
+            return self.narrow(fixed, right);
+        }
+
+        let right = self.narrow(&with_fixed(left), right);
+        let left = self.narrow(&with_fixed(&right), left);
+        left.into_iter().chain(right).collect()
+    }
+}
+
+fn write(path: &Path, content: &str) {
+    fs::write(path, content).unwrap_or_else(|e| panic!("Unable to write {}: {}", path.display(), e));
+}
+
+/// Checks that every crate in `dependencies` builds for the
+/// playground's platform, getting them from the same places as the
+/// crate list was generated from. `work_dir` holds the project and
+/// its build artifacts.
+pub fn verify(
+    dependencies: &BTreeMap<String, DependencySpec>,
+    modifications: &Modifications,
+    inputs: &Inputs,
+    work_dir: &Path,
+) -> VerificationReport {
+    let project = work_dir.join("verify");
+    fs::create_dir_all(project.join("src"))
+        .unwrap_or_else(|e| panic!("Unable to create {}: {}", project.display(), e));
+    fs::create_dir_all(project.join(".cargo"))
+        .unwrap_or_else(|e| panic!("Unable to create {}: {}", project.display(), e));
+
+    let cargo_config = CargoConfig {
+        registries: modifications
+            .registries
+            .iter()
+            .map(|(name, index)| (&**name, Registry { index }))
+            .collect(),
+    };
+    let mut cargo_config = toml::to_string(&cargo_config).expect("Couldn't serialize TOML");
+    if let Some(path) = &inputs.local_registry {
+        cargo_config.push('\n');
+        cargo_config.push_str(&crate::local_registry_source(path));
+    }
+    write(&project.join(".cargo/config.toml"), &cargo_config);
+
+    let project = Project {
+        dependencies,
+        path: project,
+    };
+    let verifier = Verifier {
+        dependencies,
+        check: |ids: &[&str]| project.check(ids),
+    };
+
+    let ids: Vec<_> = dependencies.keys().map(|id| &**id).collect();
+    verifier.run(&ids)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dependencies(ids: &[&str]) -> BTreeMap<String, DependencySpec> {
+        ids.iter()
+            .map(|&id| {
+                let spec = DependencySpec {
+                    package: String::new(),
+                    version: "1.0.0".into(),
+                    features: Vec::new(),
+                    default_features: true,
+                    registry: None,
+                    git: None,
+                    branch: None,
+                    tag: None,
+                    rev: None,
+                    path: None,
+                };
+                (id.to_string(), spec)
+            })
+            .collect()
+    }
+
+    /// Fails whenever every crate of any one of `failures` is present
+    fn verify_with(ids: &[&str], failures: &[&[&str]]) -> VerificationReport {
+        let dependencies = dependencies(ids);
+        let verifier = Verifier {
+            dependencies: &dependencies,
+            check: |ids: &[&str]| {
+                match failures.iter().find(|failure| failure.iter().all(|f| ids.contains(f))) {
+                    Some(failure) => Err(failure.join("+")),
+                    None => Ok(()),
+                }
+            },
+        };
+        verifier.run(ids)
+    }
+
+    const IDS: &[&str] = &["a", "b", "c", "d", "e", "f", "g", "h"];
+
+    #[test]
+    fn everything_builds() {
+        let report = verify_with(IDS, &[]);
+        assert!(report.success);
+        assert!(report.broken.is_empty());
+        assert!(report.conflicts.is_empty());
+    }
+
+    #[test]
+    fn broken_crates_isolated() {
+        let report = verify_with(IDS, &[&["b"], &["g"]]);
+
+        assert!(!report.success);
+        let broken: Vec<_> = report.broken.iter().map(|b| (b.id.as_str(), b.stderr.as_str())).collect();
+        assert_eq!(broken, [("b", "b"), ("g", "g")]);
+        assert_eq!(report.exclusions, ["b", "g"]);
+        assert!(report.conflicts.is_empty());
+    }
+
+    #[test]
+    fn conflicts_across_halves_narrowed() {
+        let report = verify_with(IDS, &[&["b", "g"]]);
+
+        assert!(report.broken.is_empty());
+        assert_eq!(report.conflicts, [["b", "g"]]);
+    }
+
+    #[test]
+    fn conflicts_of_several_crates_narrowed() {
+        let report = verify_with(IDS, &[&["a", "c", "f", "h"]]);
+
+        assert_eq!(report.conflicts, [["a", "c", "f", "h"]]);
+    }
+
+    #[test]
+    fn failures_after_conflicts_found() {
+        let report = verify_with(IDS, &[&["a", "e"], &["c", "h"]]);
+
+        assert!(report.broken.is_empty());
+        assert_eq!(report.conflicts, [["a", "e"], ["c", "h"]]);
+    }
+}
@@ -17,8 +17,18 @@ export interface Crate {
   id: string;
   name: string;
   version: string;
+  description: string | null;
+  documentation: string | null;
+  repository: string | null;
+  license: string | null;
+  features: string[];
+  availableFeatures: string[];
+  sources: CrateSource[];
+  aliases: string[];
 }
 
+export type CrateSource = 'topDownloads' | 'cookbook' | 'addition';
+
 export interface Version {
   version: string;
   hash: string;
@@ -7,6 +7,17 @@ use regex::{Captures, Regex};
 use rustc_demangle::demangle;
 use std::collections::HashSet;
 
+lazy_static! {
+    // Example:    mov rax, rdx
+    // Always inlude in results
+    static ref OPCODE_REGEX: Regex = Regex::new(r"^\s+[a-zA-Z]+.*[^:]$").unwrap();
+}
+
+// Produces the path without the trailing hash
+pub fn demangle_symbol(symbol: &str) -> String {
+    format!("{:#}", demangle(symbol))
+}
+
 pub fn demangle_asm(block: &str) -> String {
     lazy_static! {
         static ref DEMANGLE_REGEX: Regex = Regex::new(r"_[a-zA-Z0-9._$]*").unwrap();
@@ -14,7 +25,7 @@ pub fn demangle_asm(block: &str) -> String {
 
     DEMANGLE_REGEX
         .replace_all(block, |caps: &Captures<'_>| {
-            format!("{:#}", demangle(caps.get(0).map_or("", |m| m.as_str())))
+            demangle_symbol(caps.get(0).map_or("", |m| m.as_str()))
         })
         .to_string()
 }
@@ -30,14 +41,17 @@ enum LineType<'a> {
 }
 
 // Removes unused labels and directives from assembly
+#[allow(dead_code)]
 pub fn filter_asm(block: &str) -> String {
+    filter_asm_tracking_origin(block).0
+}
+
+// Removes unused labels and directives from assembly, reporting which
+// line of the input each line of the output came from. The blank lines
+// inserted before labels have no origin.
+pub fn filter_asm_tracking_origin(block: &str) -> (String, Vec<Option<usize>>) {
     use self::LineType::*;
 
-    lazy_static! {
-        // Example:    mov rax, rdx
-        // Always inlude in results
-        static ref OPCODE_REGEX: Regex = Regex::new(r"^\s+[a-zA-Z]+.*[^:]$").unwrap();
-    }
     lazy_static! {
         // Example:.Lfunc_end7:
         // Finds label declarations
@@ -139,26 +153,87 @@ pub fn filter_asm(block: &str) -> String {
     used_labels.extend(&data_labels);
 
     let mut filtered_asm = String::new();
-    for (line, line_type) in block.lines().zip(&line_info) {
+    let mut origins = Vec::new();
+    for (index, (line, line_type)) in block.lines().zip(&line_info).enumerate() {
         match *line_type {
             Opcode | Misc => {
                 filtered_asm.push_str(line);
                 filtered_asm.push('\n');
+                origins.push(Some(index));
             }
             Data(data) if used_labels.contains(&data) => {
                 filtered_asm.push_str(line);
                 filtered_asm.push('\n');
+                origins.push(Some(index));
             }
             LabelDecl(label) if used_labels.contains(&label) => {
                 filtered_asm.push('\n');
                 filtered_asm.push_str(line);
                 filtered_asm.push('\n');
+                origins.push(None);
+                origins.push(Some(index));
             }
             _ => (),
         }
     }
 
-    filtered_asm
+    (filtered_asm, origins)
+}
+
+// Finds the line of the user's source file that each line of assembly
+// was generated from. This relies on the `.file` and `.loc`
+// directives emitted when debug information is enabled; only opcodes
+// are attributed to a source line.
+pub fn source_lines(block: &str, source_file: &str) -> Vec<Option<u32>> {
+    lazy_static! {
+        // Example:    .file   1 "/playground" "src/main.rs"
+        // Example:    .file   2 "/rustc/abcdef/library/core/src/fmt/mod.rs"
+        // Associates a file number with a path
+        static ref FILE_REGEX: Regex = Regex::new(r#"^\s+\.file\s+(\d+)\s+"([^"]*)"(?:\s+"([^"]*)")?"#).unwrap();
+    }
+    lazy_static! {
+        // Example:    .loc    1 3 5 prologue_end
+        // Sets the file number and line for the following opcodes
+        static ref LOC_REGEX: Regex = Regex::new(r"^\s+\.loc\s+(\d+)\s+(\d+)").unwrap();
+    }
+    lazy_static! {
+        // Example:.Lfunc_end7:
+        // Nothing after the end of a function belongs to its source
+        static ref FUNCTION_END_REGEX: Regex = Regex::new(r"^\.Lfunc_end\d+:").unwrap();
+    }
+
+    let mut source_file_numbers = HashSet::new();
+    let mut current_line = None;
+
+    block
+        .lines()
+        .map(|line| {
+            if let Some(file_cap) = FILE_REGEX.captures(line) {
+                let path = file_cap
+                    .get(3)
+                    .or_else(|| file_cap.get(2))
+                    .map_or("", |m| m.as_str());
+                if path.ends_with(source_file) {
+                    source_file_numbers.insert(file_cap[1].to_string());
+                }
+                None
+            } else if let Some(loc_cap) = LOC_REGEX.captures(line) {
+                current_line = match loc_cap[2].parse() {
+                    Ok(0) | Err(_) => None,
+                    Ok(_) if !source_file_numbers.contains(&loc_cap[1]) => None,
+                    Ok(n) => Some(n),
+                };
+                None
+            } else if FUNCTION_END_REGEX.is_match(line) {
+                current_line = None;
+                None
+            } else if OPCODE_REGEX.is_match(line) {
+                current_line
+            } else {
+                None
+            }
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -290,4 +365,44 @@ mod test {
             "# %bb.0:\n subq $24, %rsp\n"
         )
     }
+    #[test]
+    fn filter_reports_origin() {
+        assert_eq!(
+            super::filter_asm_tracking_origin(".Lcfi0:\n .cfi_startproc\n mov .Lcfi0\n"),
+            (
+                "\n.Lcfi0:\n mov .Lcfi0\n".to_string(),
+                vec![None, Some(0), Some(2)]
+            )
+        );
+    }
+    #[test]
+    fn source_lines_follow_loc() {
+        assert_eq!(
+            super::source_lines(
+                " .file 1 \"/playground\" \"src/main.rs\"\n .loc 1 3 5\n pushq %rax\n .loc 1 4 0\n popq %rax\n",
+                "src/main.rs"
+            ),
+            vec![None, None, Some(3), None, Some(4)]
+        );
+    }
+    #[test]
+    fn source_lines_ignore_other_files() {
+        assert_eq!(
+            super::source_lines(
+                " .file 1 \"src/main.rs\"\n .file 2 \"/rustc/library/core/src/fmt/mod.rs\"\n .loc 2 10 0\n pushq %rax\n",
+                "src/main.rs"
+            ),
+            vec![None, None, None, None]
+        );
+    }
+    #[test]
+    fn source_lines_end_with_function() {
+        assert_eq!(
+            super::source_lines(
+                " .file 1 \"src/main.rs\"\n .loc 1 3 0\n retq\n.Lfunc_end0:\n ud2\n",
+                "src/main.rs"
+            ),
+            vec![None, None, Some(3), None, None]
+        );
+    }
 }
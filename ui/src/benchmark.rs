@@ -0,0 +1,148 @@
+// Summarizes repeated runs of libtest's `#[bench]` harness. Each run
+// reports one median time per benchmark; running the harness several
+// times lets us describe how much those medians vary.
+
+use serde_derive::Deserialize;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Benchmark {
+    pub name: String,
+    /// Nanoseconds per iteration, across all samples
+    pub mean: f64,
+    pub median: f64,
+    pub std_dev: f64,
+    pub min: f64,
+    pub max: f64,
+    /// The number of times the harness measured this benchmark. Each
+    /// sample is itself the median of many iterations.
+    pub samples: usize,
+    pub mib_per_second: Option<f64>,
+}
+
+// Emitted by `--format json -Z unstable-options`:
+//
+// { "type": "bench", "name": "tests::bench_add", "median": 12, "deviation": 1 }
+#[derive(Debug, Deserialize)]
+struct Event {
+    #[serde(rename = "type")]
+    kind: String,
+    name: Option<String>,
+    median: Option<f64>,
+    mib_per_second: Option<f64>,
+}
+
+struct Samples {
+    name: String,
+    times: Vec<f64>,
+    mib_per_second: Vec<f64>,
+}
+
+pub fn summarize(output: &str) -> Vec<Benchmark> {
+    let mut all_samples: Vec<Samples> = Vec::new();
+
+    // Anything the benchmarks themselves print is interleaved with
+    // the events, so skip whatever isn't one
+    let events = output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Event>(line).ok())
+        .filter(|event| event.kind == "bench");
+
+    for event in events {
+        let (name, median) = match (event.name, event.median) {
+            (Some(name), Some(median)) => (name, median),
+            _ => continue,
+        };
+
+        let index = match all_samples.iter().position(|s| s.name == name) {
+            Some(index) => index,
+            None => {
+                all_samples.push(Samples {
+                    name,
+                    times: Vec::new(),
+                    mib_per_second: Vec::new(),
+                });
+                all_samples.len() - 1
+            }
+        };
+
+        let samples = &mut all_samples[index];
+        samples.times.push(median);
+        samples.mib_per_second.extend(event.mib_per_second);
+    }
+
+    all_samples.into_iter().map(statistics).collect()
+}
+
+fn statistics(samples: Samples) -> Benchmark {
+    let Samples {
+        name,
+        mut times,
+        mib_per_second,
+    } = samples;
+
+    times.sort_by(|a, b| a.partial_cmp(b).expect("Times are never NaN"));
+
+    let count = times.len();
+    let mean = times.iter().sum::<f64>() / count as f64;
+    let median = if count % 2 == 0 {
+        (times[count / 2 - 1] + times[count / 2]) / 2.0
+    } else {
+        times[count / 2]
+    };
+    let std_dev = if count > 1 {
+        let variance = times.iter().map(|t| (t - mean).powi(2)).sum::<f64>() / (count - 1) as f64;
+        variance.sqrt()
+    } else {
+        0.0
+    };
+
+    let mib_per_second = if mib_per_second.is_empty() {
+        None
+    } else {
+        Some(mib_per_second.iter().sum::<f64>() / mib_per_second.len() as f64)
+    };
+
+    Benchmark {
+        name,
+        mean,
+        median,
+        std_dev,
+        min: times[0],
+        max: times[count - 1],
+        samples: count,
+        mib_per_second,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn samples_combined_per_benchmark() {
+        let output = r#"{ "type": "suite", "event": "started", "test_count": 2 }
+{ "type": "bench", "name": "a", "median": 10, "deviation": 1 }
+printed by the benchmark
+{ "type": "bench", "name": "b", "median": 100, "deviation": 5, "mib_per_second": 20 }
+{ "type": "bench", "name": "a", "median": 14, "deviation": 2 }
+{ "type": "bench", "name": "a", "median": 12, "deviation": 1 }
+"#;
+        let benchmarks = summarize(output);
+
+        assert_eq!(benchmarks.len(), 2);
+
+        let a = &benchmarks[0];
+        assert_eq!(a.name, "a");
+        assert_eq!(a.samples, 3);
+        assert_eq!(a.mean, 12.0);
+        assert_eq!(a.median, 12.0);
+        assert_eq!(a.std_dev, 2.0);
+        assert_eq!((a.min, a.max), (10.0, 14.0));
+        assert_eq!(a.mib_per_second, None);
+
+        let b = &benchmarks[1];
+        assert_eq!(b.samples, 1);
+        assert_eq!(b.std_dev, 0.0);
+        assert_eq!(b.mib_per_second, Some(20.0));
+    }
+}
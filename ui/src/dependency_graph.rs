@@ -0,0 +1,325 @@
+// Answers questions about the preinstalled crates using the resolved
+// dependency graph reported by `cargo metadata`.
+
+use serde_derive::Deserialize;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyGraph {
+    packages: BTreeMap<String, Package>,
+    top_crates: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Package {
+    /// `name@version`, unique within the graph
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    /// The features enabled by the resolver
+    pub features: Vec<String>,
+    /// The ids of the packages this one depends on
+    pub dependencies: Vec<String>,
+}
+
+/// Every version of a crate and everything they depend on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependencies {
+    pub roots: Vec<String>,
+    pub packages: Vec<Package>,
+}
+
+/// How one version of a crate came to be present
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependents {
+    pub package: String,
+    /// For each top crate that pulls in the package, the shortest chain
+    /// of package ids from the top crate to the package
+    pub paths: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Metadata {
+    packages: Vec<MetadataPackage>,
+    resolve: Option<Resolve>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataPackage {
+    id: String,
+    name: String,
+    version: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Resolve {
+    nodes: Vec<Node>,
+    root: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Node {
+    id: String,
+    dependencies: Vec<String>,
+    #[serde(default)]
+    features: Vec<String>,
+}
+
+// Crates are requested by either their package name or their crate
+// name, such as `proc-macro2` or `proc_macro2`
+fn same_crate(a: &str, b: &str) -> bool {
+    a.replace('-', "_") == b.replace('-', "_")
+}
+
+impl DependencyGraph {
+    /// Parses the output of `cargo metadata --format-version 1`
+    pub fn from_metadata(json: &[u8]) -> serde_json::Result<Self> {
+        let metadata: Metadata = serde_json::from_slice(json)?;
+
+        let ids: BTreeMap<_, _> = metadata
+            .packages
+            .iter()
+            .map(|p| (p.id.as_str(), format!("{}@{}", p.name, p.version)))
+            .collect();
+        let id = |metadata_id: &str| ids.get(metadata_id).cloned();
+
+        let resolve = match metadata.resolve {
+            Some(resolve) => resolve,
+            None => {
+                return Ok(DependencyGraph {
+                    packages: BTreeMap::new(),
+                    top_crates: Vec::new(),
+                })
+            }
+        };
+
+        let root = resolve.root.as_deref();
+        let mut top_crates = Vec::new();
+        let mut packages = BTreeMap::new();
+
+        for node in resolve.nodes {
+            let dependencies = node.dependencies.iter().filter_map(|d| id(d)).collect();
+
+            if Some(&*node.id) == root {
+                top_crates = dependencies;
+                continue;
+            }
+
+            let package = match metadata.packages.iter().find(|p| p.id == node.id) {
+                Some(package) => package,
+                None => continue,
+            };
+            let id = format!("{}@{}", package.name, package.version);
+
+            packages.insert(
+                id.clone(),
+                Package {
+                    id,
+                    name: package.name.clone(),
+                    version: package.version.clone(),
+                    features: node.features,
+                    dependencies,
+                },
+            );
+        }
+
+        Ok(DependencyGraph {
+            packages,
+            top_crates,
+        })
+    }
+
+    fn versions_of<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Package> + 'a {
+        self.packages
+            .values()
+            .filter(move |p| same_crate(&p.name, name))
+    }
+
+    /// Returns `None` when no version of the crate is present
+    pub fn dependencies(&self, name: &str) -> Option<Dependencies> {
+        let roots: Vec<_> = self.versions_of(name).map(|p| p.id.clone()).collect();
+        if roots.is_empty() {
+            return None;
+        }
+
+        let mut seen = BTreeSet::new();
+        let mut queue: VecDeque<_> = roots.iter().collect();
+        let mut packages = Vec::new();
+
+        while let Some(id) = queue.pop_front() {
+            if !seen.insert(id) {
+                continue;
+            }
+            if let Some(package) = self.packages.get(id) {
+                queue.extend(&package.dependencies);
+                packages.push(package.clone());
+            }
+        }
+
+        Some(Dependencies { roots, packages })
+    }
+
+    /// Returns `None` when no version of the crate is present
+    pub fn dependents(&self, name: &str) -> Option<Vec<Dependents>> {
+        let dependents: Vec<_> = self
+            .versions_of(name)
+            .map(|package| Dependents {
+                package: package.id.clone(),
+                paths: self
+                    .top_crates
+                    .iter()
+                    .filter_map(|top| self.shortest_path(top, &package.id))
+                    .collect(),
+            })
+            .collect();
+
+        if dependents.is_empty() {
+            None
+        } else {
+            Some(dependents)
+        }
+    }
+
+    fn shortest_path(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        let mut previous: BTreeMap<&str, &str> = BTreeMap::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(from);
+        previous.insert(from, from);
+
+        while let Some(id) = queue.pop_front() {
+            if id == to {
+                let mut path = vec![id.to_string()];
+                let mut current = id;
+                while current != from {
+                    current = previous[current];
+                    path.push(current.to_string());
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            let package = match self.packages.get(id) {
+                Some(package) => package,
+                None => continue,
+            };
+            for dependency in &package.dependencies {
+                if !previous.contains_key(&**dependency) {
+                    previous.insert(dependency, id);
+                    queue.push_back(dependency);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Trimmed from real output; `cargo metadata` includes many more
+    // fields for each package and node
+    const METADATA: &str = r#"{
+      "packages": [
+        { "id": "playground 0.0.1 (path+file:///playground)", "name": "playground", "version": "0.0.1" },
+        { "id": "syn 1.0.86 (registry+https://github.com/rust-lang/crates.io-index)", "name": "syn", "version": "1.0.86" },
+        { "id": "quote 1.0.15 (registry+https://github.com/rust-lang/crates.io-index)", "name": "quote", "version": "1.0.15" },
+        { "id": "proc-macro2 1.0.36 (registry+https://github.com/rust-lang/crates.io-index)", "name": "proc-macro2", "version": "1.0.36" },
+        { "id": "unicode-xid 0.2.2 (registry+https://github.com/rust-lang/crates.io-index)", "name": "unicode-xid", "version": "0.2.2" }
+      ],
+      "resolve": {
+        "root": "playground 0.0.1 (path+file:///playground)",
+        "nodes": [
+          {
+            "id": "playground 0.0.1 (path+file:///playground)",
+            "dependencies": [
+              "quote 1.0.15 (registry+https://github.com/rust-lang/crates.io-index)",
+              "syn 1.0.86 (registry+https://github.com/rust-lang/crates.io-index)"
+            ],
+            "features": []
+          },
+          {
+            "id": "syn 1.0.86 (registry+https://github.com/rust-lang/crates.io-index)",
+            "dependencies": [
+              "proc-macro2 1.0.36 (registry+https://github.com/rust-lang/crates.io-index)",
+              "quote 1.0.15 (registry+https://github.com/rust-lang/crates.io-index)"
+            ],
+            "features": ["full", "parsing"]
+          },
+          {
+            "id": "quote 1.0.15 (registry+https://github.com/rust-lang/crates.io-index)",
+            "dependencies": [
+              "proc-macro2 1.0.36 (registry+https://github.com/rust-lang/crates.io-index)"
+            ],
+            "features": ["proc-macro"]
+          },
+          {
+            "id": "proc-macro2 1.0.36 (registry+https://github.com/rust-lang/crates.io-index)",
+            "dependencies": [
+              "unicode-xid 0.2.2 (registry+https://github.com/rust-lang/crates.io-index)"
+            ],
+            "features": ["proc-macro"]
+          },
+          {
+            "id": "unicode-xid 0.2.2 (registry+https://github.com/rust-lang/crates.io-index)",
+            "dependencies": [],
+            "features": ["default"]
+          }
+        ]
+      }
+    }"#;
+
+    fn graph() -> DependencyGraph {
+        DependencyGraph::from_metadata(METADATA.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn dependencies_include_features() {
+        let dependencies = graph().dependencies("syn").unwrap();
+
+        assert_eq!(dependencies.roots, ["syn@1.0.86"]);
+
+        let ids: Vec<_> = dependencies.packages.iter().map(|p| &*p.id).collect();
+        assert_eq!(
+            ids,
+            [
+                "syn@1.0.86",
+                "proc-macro2@1.0.36",
+                "quote@1.0.15",
+                "unicode-xid@0.2.2"
+            ]
+        );
+        assert_eq!(dependencies.packages[0].features, ["full", "parsing"]);
+    }
+
+    #[test]
+    fn dependents_traced_to_top_crates() {
+        let dependents = graph().dependents("unicode_xid").unwrap();
+
+        assert_eq!(
+            dependents,
+            [Dependents {
+                package: "unicode-xid@0.2.2".to_string(),
+                paths: vec![
+                    vec![
+                        "quote@1.0.15".to_string(),
+                        "proc-macro2@1.0.36".to_string(),
+                        "unicode-xid@0.2.2".to_string(),
+                    ],
+                    vec![
+                        "syn@1.0.86".to_string(),
+                        "proc-macro2@1.0.36".to_string(),
+                        "unicode-xid@0.2.2".to_string(),
+                    ],
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn unknown_crates() {
+        assert_eq!(graph().dependencies("rand"), None);
+        assert_eq!(graph().dependents("rand"), None);
+    }
+}
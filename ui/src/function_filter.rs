@@ -0,0 +1,367 @@
+// Locates the functions in compiler output so that the output can be
+// limited to the ones the user is interested in
+
+use crate::asm_cleanup::demangle_symbol;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    // The half-open range of lines of the unprocessed output
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    pub code: String,
+    // The line of the input each line of `code` came from
+    pub origins: Vec<Option<usize>>,
+    // Every function found, with its 1-based inclusive line range
+    // in `code` if it was selected
+    pub functions: Vec<(String, Option<(u32, u32)>)>,
+}
+
+pub fn asm_functions(block: &str) -> Vec<Function> {
+    lazy_static! {
+        // Example:    .type   _ZN10playground4main17h0123456789abcdefE,@function
+        static ref FUNCTION_DECL_REGEX: Regex = Regex::new(r"^\s+\.type\s+([^,\s]+),\s*@function").unwrap();
+    }
+    lazy_static! {
+        // Example:_ZN10playground4main17h0123456789abcdefE:
+        static ref LABEL_DECL_REGEX: Regex = Regex::new(r"^([^\s:]+):").unwrap();
+    }
+    lazy_static! {
+        // Example:    .size   _ZN10playground4main17h0123456789abcdefE, .Lfunc_end0-_ZN10playground4main17h0123456789abcdefE
+        static ref SIZE_REGEX: Regex = Regex::new(r"^\s+\.size\s+([^,\s]+),").unwrap();
+    }
+
+    let lines: Vec<_> = block.lines().collect();
+    let declared: HashSet<_> = lines
+        .iter()
+        .filter_map(|line| FUNCTION_DECL_REGEX.captures(line))
+        .map(|cap| cap.get(1).unwrap().as_str())
+        .collect();
+
+    let mut functions: Vec<Function> = Vec::new();
+    let mut current: Option<(&str, usize)> = None;
+
+    for (index, line) in lines.iter().enumerate() {
+        if let Some(label_cap) = LABEL_DECL_REGEX.captures(line) {
+            let label = label_cap.get(1).unwrap().as_str();
+            if declared.contains(label) {
+                if let Some((symbol, start)) = current.take() {
+                    functions.push(Function {
+                        name: demangle_symbol(symbol),
+                        start,
+                        end: index,
+                    });
+                }
+                current = Some((label, index));
+            }
+        } else if let Some(size_cap) = SIZE_REGEX.captures(line) {
+            if let Some((symbol, start)) = current {
+                if symbol == &size_cap[1] {
+                    let next_is_endproc =
+                        matches!(lines.get(index + 1), Some(l) if l.trim() == ".cfi_endproc");
+                    let end = if next_is_endproc {
+                        index + 2
+                    } else {
+                        index + 1
+                    };
+
+                    functions.push(Function {
+                        name: demangle_symbol(symbol),
+                        start,
+                        end,
+                    });
+                    current = None;
+                }
+            }
+        }
+    }
+
+    if let Some((symbol, start)) = current {
+        functions.push(Function {
+            name: demangle_symbol(symbol),
+            start,
+            end: lines.len(),
+        });
+    }
+
+    functions
+}
+
+pub fn llvm_ir_functions(block: &str) -> Vec<Function> {
+    lazy_static! {
+        // Example:define internal void @_ZN10playground4main17h0123456789abcdefE() unnamed_addr #1 {
+        // Example:define void @"_ZN10playground3foo$LT$T$GT$17h0123456789abcdefE"(i32 %x) {
+        static ref DEFINE_REGEX: Regex = Regex::new(r#"^define\b[^@]*@(?:"([^"]+)"|([-a-zA-Z$._0-9]+))\("#).unwrap();
+    }
+
+    let lines: Vec<_> = block.lines().collect();
+    let mut functions = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        let define_cap = match DEFINE_REGEX.captures(line) {
+            Some(cap) => cap,
+            None => continue,
+        };
+        let symbol = define_cap.get(1).or_else(|| define_cap.get(2)).unwrap();
+
+        // Include the comments that name the function and list its
+        // attributes
+        let start = lines[..index]
+            .iter()
+            .rposition(|l| !l.starts_with(';'))
+            .map_or(0, |p| p + 1);
+        let end = lines[index..]
+            .iter()
+            .position(|l| *l == "}")
+            .map_or(lines.len(), |p| index + p + 1);
+
+        functions.push(Function {
+            name: demangle_symbol(symbol.as_str()),
+            start,
+            end,
+        });
+    }
+
+    functions
+}
+
+pub fn mir_functions(block: &str) -> Vec<Function> {
+    lazy_static! {
+        // Example:fn main() -> () {
+        // Example:fn <impl at src/main.rs:3:1: 3:9>::new(_1: i32) -> Foo {
+        static ref FN_REGEX: Regex = Regex::new(r"^fn ([^(]+)\(").unwrap();
+    }
+    lazy_static! {
+        // Example:static FOO: i32 = {
+        // Example:const BAR: &str = {
+        static ref CONST_REGEX: Regex = Regex::new(r"^(?:static mut|static|const) (.+?): ").unwrap();
+    }
+
+    let lines: Vec<_> = block.lines().collect();
+    let mut functions: Vec<Function> = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        let header_cap = FN_REGEX
+            .captures(line)
+            .or_else(|| CONST_REGEX.captures(line));
+        let header_cap = match header_cap {
+            Some(cap) => cap,
+            None => continue,
+        };
+
+        if let Some(previous) = functions.last_mut() {
+            previous.end = index;
+        }
+        functions.push(Function {
+            name: header_cap[1].trim().to_string(),
+            start: index,
+            end: lines.len(),
+        });
+    }
+
+    // Each item runs until the next one, which includes any promoted
+    // constants that follow it. Leave the separating blank lines out.
+    for function in &mut functions {
+        while function.end > function.start && lines[function.end - 1].trim().is_empty() {
+            function.end -= 1;
+        }
+    }
+
+    functions
+}
+
+fn glob_regex(pattern: &str) -> Regex {
+    let mut regex = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    regex.push('$');
+
+    Regex::new(&regex).expect("An escaped pattern is always valid")
+}
+
+// A pattern matches the complete path or any trailing part of it, so
+// `main` matches `playground::main`
+fn matches(name: &str, patterns: &[Regex]) -> bool {
+    let suffixes = name
+        .match_indices("::")
+        .map(|(i, sep)| &name[i + sep.len()..]);
+
+    std::iter::once(name)
+        .chain(suffixes)
+        .any(|candidate| patterns.iter().any(|p| p.is_match(candidate)))
+}
+
+// Keeps only the lines belonging to functions that match any of the
+// patterns, or every line when there are no patterns. `origins`
+// relates each line of `code` to the line of the unprocessed output
+// that the functions were found in.
+pub fn select(
+    code: &str,
+    origins: &[Option<usize>],
+    functions: &[Function],
+    patterns: &[String],
+) -> Selection {
+    let patterns: Vec<_> = patterns.iter().map(|p| glob_regex(p)).collect();
+    let selected: Vec<_> = functions
+        .iter()
+        .map(|f| patterns.is_empty() || matches(&f.name, &patterns))
+        .collect();
+
+    let owner_of = |origin: usize| {
+        functions
+            .iter()
+            .position(|f| f.start <= origin && origin < f.end)
+    };
+
+    // Lines without an origin, such as the blank lines inserted
+    // before labels, belong with the line that follows them
+    let mut owners = vec![None; origins.len()];
+    let mut next_owner = None;
+    for (index, origin) in origins.iter().enumerate().rev() {
+        if let Some(origin) = origin {
+            next_owner = owner_of(*origin);
+        }
+        owners[index] = next_owner;
+    }
+
+    let mut selection = Selection {
+        code: String::new(),
+        origins: Vec::new(),
+        functions: Vec::new(),
+    };
+    let mut ranges = vec![None; functions.len()];
+    let mut previous_owner = None;
+
+    for (index, (line, owner)) in code.lines().zip(&owners).enumerate() {
+        let keep = patterns.is_empty() || matches!(owner, Some(o) if selected[*o]);
+        if !keep {
+            continue;
+        }
+
+        let starts_new_function = owner.is_some() && *owner != previous_owner;
+        let needs_separator = !selection.code.is_empty() && !selection.code.ends_with("\n\n");
+        if !patterns.is_empty() && starts_new_function && needs_separator && !line.is_empty() {
+            selection.code.push('\n');
+            selection.origins.push(None);
+        }
+        previous_owner = *owner;
+
+        selection.code.push_str(line);
+        selection.code.push('\n');
+        selection.origins.push(Some(index));
+
+        if let Some(owner) = owner {
+            let line_number = selection.origins.len() as u32;
+            let range = ranges[*owner].get_or_insert((line_number, line_number));
+            range.1 = line_number;
+        }
+    }
+
+    selection.functions = functions
+        .iter()
+        .zip(ranges)
+        .map(|(f, range)| (f.name.clone(), range))
+        .collect();
+
+    selection
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn names(functions: &[Function]) -> Vec<&str> {
+        functions.iter().map(|f| f.name.as_str()).collect()
+    }
+
+    fn identity(code: &str) -> Vec<Option<usize>> {
+        (0..code.lines().count()).map(Some).collect()
+    }
+
+    const ASM: &str = "\t.section .text._ZN10playground3foo17h0123456789abcdefE\n\t.type _ZN10playground3foo17h0123456789abcdefE,@function\n_ZN10playground3foo17h0123456789abcdefE:\n\tretq\n.Lfunc_end0:\n\t.size _ZN10playground3foo17h0123456789abcdefE, .Lfunc_end0-_ZN10playground3foo17h0123456789abcdefE\n\t.cfi_endproc\n\t.type main,@function\nmain:\n\tcallq _ZN10playground3foo17h0123456789abcdefE\n.Lfunc_end1:\n\t.size main, .Lfunc_end1-main\n";
+
+    #[test]
+    fn asm_functions_found() {
+        let functions = asm_functions(ASM);
+        assert_eq!(names(&functions), ["playground::foo", "main"]);
+        assert_eq!((functions[0].start, functions[0].end), (2, 7));
+        assert_eq!((functions[1].start, functions[1].end), (8, 12));
+    }
+
+    #[test]
+    fn llvm_ir_functions_found() {
+        let ir = "; ModuleID = 'playground.7rcbfp3g-cgu.0'\n\n; playground::foo\n; Function Attrs: nounwind\ndefine internal void @_ZN10playground3foo17h0123456789abcdefE() {\nstart:\n  ret void\n}\n\ndefine i32 @\"main\"(i32 %0, i8** %1) {\n  ret i32 0\n}\n";
+        let functions = llvm_ir_functions(ir);
+        assert_eq!(names(&functions), ["playground::foo", "main"]);
+        assert_eq!((functions[0].start, functions[0].end), (2, 8));
+        assert_eq!((functions[1].start, functions[1].end), (9, 12));
+    }
+
+    #[test]
+    fn mir_promoted_belong_to_function() {
+        let mir = "// WARNING: This output format is intended for human consumers only\nfn foo() -> &i32 {\n    bb0: {\n    }\n}\n\npromoted[0] in foo: &i32 = {\n}\n\nstatic BAR: i32 = {\n}\n";
+        let functions = mir_functions(mir);
+        assert_eq!(names(&functions), ["foo", "BAR"]);
+        assert_eq!((functions[0].start, functions[0].end), (1, 8));
+        assert_eq!((functions[1].start, functions[1].end), (9, 11));
+    }
+
+    #[test]
+    fn glob_matches_path_suffix() {
+        assert!(matches("playground::foo", &[glob_regex("foo")]));
+        assert!(matches("playground::Foo::new", &[glob_regex("Foo::*")]));
+        assert!(matches("playground::foo", &[glob_regex("play*::f?o")]));
+        assert!(!matches("playground::foobar", &[glob_regex("foo")]));
+    }
+
+    #[test]
+    fn select_one_function() {
+        let functions = asm_functions(ASM);
+        let selection = select(ASM, &identity(ASM), &functions, &["main".to_string()]);
+
+        assert_eq!(
+            selection.code,
+            "main:\n\tcallq _ZN10playground3foo17h0123456789abcdefE\n.Lfunc_end1:\n\t.size main, .Lfunc_end1-main\n"
+        );
+        assert_eq!(selection.origins, [Some(8), Some(9), Some(10), Some(11)]);
+        assert_eq!(
+            selection.functions,
+            [
+                ("playground::foo".to_string(), None),
+                ("main".to_string(), Some((1, 4)))
+            ]
+        );
+    }
+
+    #[test]
+    fn select_without_patterns_keeps_everything() {
+        let functions = asm_functions(ASM);
+        let selection = select(ASM, &identity(ASM), &functions, &[]);
+
+        assert_eq!(selection.code, ASM);
+        assert_eq!(selection.functions[0].1, Some((3, 7)));
+    }
+
+    #[test]
+    fn select_follows_origins() {
+        let code = "\nmain:\n\tcallq foo\n";
+        let origins = [None, Some(8), Some(9)];
+        let functions = asm_functions(ASM);
+        let selection = select(code, &origins, &functions, &["main".to_string()]);
+
+        assert_eq!(selection.code, code);
+        assert_eq!(selection.functions[1].1, Some((1, 3)));
+    }
+}
@@ -0,0 +1,179 @@
+// Serves documentation generated by the sandbox for a limited time.
+// Each archive is unpacked into its own randomly-named directory
+// beneath a single root that the web server exposes.
+
+use flate2::read::GzDecoder;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tempdir::TempDir;
+
+/// rustdoc output for the playground crate and its dependencies is a
+/// few megabytes, so anything much larger is refused.
+const MAX_UNPACKED_SIZE: u64 = 100 * 1024 * 1024;
+
+pub(crate) struct HostedDocs {
+    root: TempDir,
+    time_to_live: Duration,
+    max_size: u64,
+    hosted: Mutex<Vec<(PathBuf, Instant)>>,
+}
+
+impl HostedDocs {
+    pub(crate) fn new(time_to_live: Duration) -> io::Result<Self> {
+        Ok(HostedDocs {
+            root: TempDir::new("playground-docs")?,
+            time_to_live,
+            max_size: MAX_UNPACKED_SIZE,
+            hosted: Default::default(),
+        })
+    }
+
+    pub(crate) fn root(&self) -> &Path {
+        self.root.path()
+    }
+
+    /// Unpacks a gzipped tarball, returning the name of the directory
+    /// that contains it.
+    pub(crate) fn host(&self, archive: &[u8]) -> io::Result<String> {
+        self.remove_expired();
+
+        let dir = TempDir::new_in(self.root(), "doc")?.into_path();
+
+        if let Err(e) = self.unpack(archive, &dir) {
+            if let Err(e) = fs::remove_dir_all(&dir) {
+                log::warn!("Unable to remove hosted docs {}: {}", dir.display(), e);
+            }
+            return Err(e);
+        }
+
+        let id = dir
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        self.hosted
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((dir, Instant::now()));
+
+        Ok(id)
+    }
+
+    fn unpack(&self, archive: &[u8], dir: &Path) -> io::Result<()> {
+        // The archive comes from inside the sandbox, so only regular
+        // files and directories are allowed out. `unpack_in` refuses
+        // paths that would land outside of the directory.
+        let mut archive = tar::Archive::new(GzDecoder::new(archive));
+        let mut size = 0;
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+
+            // Directories still use disk space, so each entry counts
+            // for at least one block.
+            size += entry.size().max(512);
+            if size > self.max_size {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "The documentation is larger than the limit of {} bytes",
+                        self.max_size
+                    ),
+                ));
+            }
+
+            let kind = entry.header().entry_type();
+            if kind.is_file() || kind.is_dir() {
+                entry.unpack_in(dir)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes all documentation that has been hosted for longer than
+    /// the time to live. This is called when new documentation is
+    /// hosted, but should also be called periodically so that old
+    /// documentation does not outlive its time when nothing new is
+    /// generated.
+    pub(crate) fn remove_expired(&self) {
+        let mut hosted = self.hosted.lock().unwrap_or_else(|e| e.into_inner());
+
+        hosted.retain(|(dir, created)| {
+            if created.elapsed() < self.time_to_live {
+                return true;
+            }
+
+            if let Err(e) = fs::remove_dir_all(dir) {
+                log::warn!("Unable to remove hosted docs {}: {}", dir.display(), e);
+            }
+            false
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+
+    fn archive(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, contents.as_bytes())
+                .unwrap();
+        }
+
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn archives_unpacked_under_root() {
+        let docs = HostedDocs::new(Duration::from_secs(60)).unwrap();
+        let archive = archive(&[("playground/index.html", "<html></html>")]);
+
+        let id = docs.host(&archive).unwrap();
+
+        let index = docs.root().join(id).join("playground/index.html");
+        assert_eq!(fs::read_to_string(index).unwrap(), "<html></html>");
+    }
+
+    #[test]
+    fn expired_docs_removed() {
+        let docs = HostedDocs::new(Duration::from_secs(0)).unwrap();
+        let archive = archive(&[("index.html", "")]);
+
+        let first = docs.host(&archive).unwrap();
+        let second = docs.host(&archive).unwrap();
+
+        assert!(!docs.root().join(first).exists());
+        assert!(docs.root().join(&second).exists());
+
+        docs.remove_expired();
+        assert!(!docs.root().join(&second).exists());
+    }
+
+    #[test]
+    fn large_archives_rejected() {
+        let mut docs = HostedDocs::new(Duration::from_secs(60)).unwrap();
+        docs.max_size = 1024;
+        let contents = "x".repeat(2048);
+        let archive = archive(&[("index.html", &contents)]);
+
+        let error = docs.host(&archive).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let remaining = fs::read_dir(docs.root()).unwrap().count();
+        assert_eq!(remaining, 0);
+    }
+}
@@ -0,0 +1,150 @@
+// Checks incoming requests before any work is done with them. A
+// request that is too large or contains code that could never compile
+// is rejected before it reaches a container.
+
+use snafu::prelude::*;
+use std::str;
+
+pub const DEFAULT_REQUEST_SIZE: usize = 1024 * 1024;
+pub const DEFAULT_CODE_SIZE: usize = 512 * 1024;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("The request is larger than the limit of {} bytes", limit))]
+    RequestTooLarge { limit: usize },
+    #[snafu(display("The request is not valid UTF-8"))]
+    RequestNotUtf8 { source: str::Utf8Error },
+    #[snafu(display("The code is {} bytes, larger than the limit of {} bytes", size, limit))]
+    CodeTooLarge { size: usize, limit: usize },
+    #[snafu(display("The code may not contain NUL bytes"))]
+    CodeContainsNul,
+}
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+impl Error {
+    /// The HTTP status code that best describes why the request was
+    /// rejected.
+    pub fn status_code(&self) -> u16 {
+        match self {
+            Error::RequestTooLarge { .. } | Error::CodeTooLarge { .. } => 413,
+            Error::RequestNotUtf8 { .. } | Error::CodeContainsNul => 400,
+        }
+    }
+
+    /// A stable, machine-readable name for why the request was
+    /// rejected
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::RequestTooLarge { .. } => "RequestTooLarge",
+            Error::RequestNotUtf8 { .. } => "RequestNotUtf8",
+            Error::CodeTooLarge { .. } => "CodeTooLarge",
+            Error::CodeContainsNul => "CodeContainsNul",
+        }
+    }
+}
+
+/// Requests which contain code that will be written to disk.
+pub trait SourceCode {
+    fn source_code(&self) -> Vec<&str>;
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Limits {
+    pub request_size: usize,
+    pub code_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            request_size: DEFAULT_REQUEST_SIZE,
+            code_size: DEFAULT_CODE_SIZE,
+        }
+    }
+}
+
+impl Limits {
+    pub fn check_request_size(&self, size: usize) -> Result<()> {
+        ensure!(
+            size <= self.request_size,
+            RequestTooLargeSnafu {
+                limit: self.request_size
+            }
+        );
+        Ok(())
+    }
+
+    pub fn check_request<'a>(&self, body: &'a [u8]) -> Result<&'a str> {
+        self.check_request_size(body.len())?;
+        str::from_utf8(body).context(RequestNotUtf8Snafu)
+    }
+
+    pub fn check_code(&self, req: &impl SourceCode) -> Result<()> {
+        let sources = req.source_code();
+
+        let size: usize = sources.iter().map(|s| s.len()).sum();
+        ensure!(
+            size <= self.code_size,
+            CodeTooLargeSnafu {
+                size,
+                limit: self.code_size
+            }
+        );
+
+        ensure!(
+            !sources.iter().any(|s| s.contains('\0')),
+            CodeContainsNulSnafu
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Code(&'static str, Option<&'static str>);
+
+    impl SourceCode for Code {
+        fn source_code(&self) -> Vec<&str> {
+            std::iter::once(self.0).chain(self.1).collect()
+        }
+    }
+
+    const LIMITS: Limits = Limits {
+        request_size: 16,
+        code_size: 8,
+    };
+
+    #[test]
+    fn small_requests_allowed() {
+        assert_eq!(LIMITS.check_request(b"{}").unwrap(), "{}");
+        assert!(LIMITS.check_code(&Code("fn main", Some("x"))).is_ok());
+    }
+
+    #[test]
+    fn large_requests_rejected() {
+        let error = LIMITS.check_request(&[b' '; 17]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The request is larger than the limit of 16 bytes"
+        );
+
+        let error = LIMITS.check_code(&Code("fn main", Some("{}"))).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The code is 9 bytes, larger than the limit of 8 bytes"
+        );
+    }
+
+    #[test]
+    fn invalid_content_rejected() {
+        let error = LIMITS.check_request(b"{\"\xff\"}").unwrap_err();
+        assert!(matches!(error, Error::RequestNotUtf8 { .. }));
+
+        let error = LIMITS.check_code(&Code("fn\0", None)).unwrap_err();
+        assert!(matches!(error, Error::CodeContainsNul));
+    }
+}
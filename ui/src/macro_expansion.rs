@@ -0,0 +1,211 @@
+// Picks individual items out of the output of `-Zunpretty=expanded`.
+//
+// The pretty-printer places every top-level item in the first column
+// and indents everything inside of it, which lets us split the output
+// into items without parsing it.
+
+const ITEM_KEYWORDS: &[&str] = &[
+    "const",
+    "enum",
+    "fn",
+    "impl",
+    "macro_rules",
+    "mod",
+    "static",
+    "struct",
+    "trait",
+    "type",
+    "union",
+];
+
+// Words that can appear between the item keyword and the item's name
+const QUALIFIERS: &[&str] = &["async", "extern", "mut", "unsafe"];
+
+struct Item<'a> {
+    lines: Vec<&'a str>,
+    header: Option<&'a str>,
+}
+
+impl<'a> Item<'a> {
+    fn kind_and_name(&self) -> Option<(&'a str, Vec<&'a str>)> {
+        let mut words = self
+            .header?
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .filter(|w| !w.is_empty());
+
+        let kind = words.by_ref().find(|w| ITEM_KEYWORDS.contains(w))?;
+        let rest = words
+            .skip_while(|w| ITEM_KEYWORDS.contains(w) || QUALIFIERS.contains(w))
+            .collect();
+
+        Some((kind, rest))
+    }
+
+    /// Items that define `name` or, for `impl` blocks, mention it.
+    /// This includes the implementations generated by `#[derive]`.
+    fn is_named(&self, name: &str) -> bool {
+        match self.kind_and_name() {
+            Some(("impl", words)) => words.contains(&name),
+            Some((_, words)) => words.first() == Some(&name),
+            None => false,
+        }
+    }
+
+    fn is_module(&self, name: &str) -> bool {
+        matches!(self.kind_and_name(), Some(("mod", words)) if words.first() == Some(&name))
+    }
+
+    // Everything between the opening line and the closing brace,
+    // moved back to the first column
+    fn module_body(&self) -> String {
+        let header = self.header.unwrap_or("");
+        let start = self.lines.iter().position(|&l| l == header).unwrap_or(0) + 1;
+        let end = self.lines.len().saturating_sub(1).max(start);
+
+        self.lines[start..end]
+            .iter()
+            .map(|l| l.strip_prefix("    ").unwrap_or(l))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+}
+
+fn is_attribute(line: &str) -> bool {
+    line.starts_with("#[") || line.starts_with("#!") || line.starts_with("//")
+}
+
+fn top_level_items(code: &str) -> Vec<Item<'_>> {
+    let mut items: Vec<Item<'_>> = Vec::new();
+
+    for line in code.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let continues_item = line.starts_with(char::is_whitespace)
+            || line.starts_with(['}', ')', ']'])
+            || matches!(items.last(), Some(item) if item.header.is_none());
+
+        match items.last_mut() {
+            Some(item) if continues_item => {
+                item.lines.push(line);
+                if item.header.is_none() && !is_attribute(line) {
+                    item.header = Some(line);
+                }
+            }
+            _ => items.push(Item {
+                lines: vec![line],
+                header: if is_attribute(line) { None } else { Some(line) },
+            }),
+        }
+    }
+
+    items
+}
+
+/// Selects the items named by `path`, such as `Widget` or
+/// `shapes::Circle`. Leading path segments must name modules.
+pub fn select_item(expanded: &str, path: &str) -> Option<String> {
+    let mut code = expanded.to_string();
+    let mut segments = path.split("::").peekable();
+
+    while let Some(name) = segments.next() {
+        let items = top_level_items(&code);
+
+        if segments.peek().is_some() {
+            let module = items.iter().find(|i| i.is_module(name))?;
+            code = module.module_body();
+        } else {
+            let selected: Vec<_> = items
+                .iter()
+                .filter(|i| i.is_named(name))
+                .map(Item::text)
+                .collect();
+
+            if selected.is_empty() {
+                return None;
+            }
+            return Some(selected.join("\n") + "\n");
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXPANDED: &str = r#"#![feature(prelude_import)]
+#[prelude_import]
+use std::prelude::rust_2018::*;
+#[macro_use]
+extern crate std;
+struct Widget {
+    size: u8,
+}
+#[automatically_derived]
+#[allow(unused_qualifications)]
+impl ::core::fmt::Debug for Widget {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+        ::core::fmt::Formatter::debug_struct_field1_finish(f, "Widget",
+            "size", &&self.size)
+    }
+}
+mod shapes {
+    use super::*;
+    pub struct Circle;
+    #[automatically_derived]
+    impl ::core::clone::Clone for Circle {
+        #[inline]
+        fn clone(&self) -> Circle { Circle }
+    }
+    pub const fn area() -> u8 { 0 }
+}
+fn main() {
+    {
+        ::std::io::_print(::core::fmt::Arguments::new_v1(&["Hello\n"], &[]));
+    };
+}
+"#;
+
+    #[test]
+    fn item_and_derived_impls_selected() {
+        let selected = select_item(EXPANDED, "Widget").unwrap();
+
+        assert!(selected.starts_with("struct Widget {"));
+        assert!(selected.contains("#[automatically_derived]"));
+        assert!(selected.contains("impl ::core::fmt::Debug for Widget {"));
+        assert!(!selected.contains("prelude"));
+        assert!(!selected.contains("fn main"));
+    }
+
+    #[test]
+    fn items_inside_modules_selected() {
+        let selected = select_item(EXPANDED, "shapes::Circle").unwrap();
+
+        assert!(selected.starts_with("pub struct Circle;\n#[automatically_derived]\nimpl"));
+        assert!(selected.contains("\n    fn clone(&self)"));
+
+        let selected = select_item(EXPANDED, "shapes::area").unwrap();
+        assert_eq!(selected, "pub const fn area() -> u8 { 0 }\n");
+    }
+
+    #[test]
+    fn whole_modules_selected() {
+        let selected = select_item(EXPANDED, "shapes").unwrap();
+
+        assert!(selected.starts_with("mod shapes {"));
+        assert!(selected.ends_with("}\n"));
+    }
+
+    #[test]
+    fn missing_items() {
+        assert_eq!(select_item(EXPANDED, "Gadget"), None);
+        assert_eq!(select_item(EXPANDED, "Widget::size"), None);
+    }
+}
@@ -1,7 +1,7 @@
 #![deny(rust_2018_idioms)]
 
 use serde::{Deserialize, Serialize};
-use snafu::Snafu;
+use snafu::{OptionExt, ResultExt, Snafu};
 use std::{
     convert::TryFrom,
     env,
@@ -16,10 +16,19 @@ const DEFAULT_PORT: u16 = 5000;
 const DEFAULT_LOG_FILE: &str = "access-log.csv";
 
 mod asm_cleanup;
+mod benchmark;
+mod dependency_graph;
+mod function_filter;
 mod gist;
+mod hosted_docs;
+mod limits;
+mod macro_expansion;
+mod manifest;
 mod sandbox;
 mod server_axum;
 mod server_iron;
+mod size_analysis;
+mod test_results;
 
 const ONE_HOUR_IN_SECONDS: u32 = 60 * 60;
 const ONE_HOUR: Duration = Duration::from_secs(ONE_HOUR_IN_SECONDS as u64);
@@ -28,6 +37,13 @@ const ONE_YEAR: Duration = Duration::from_secs(60 * 60 * 24 * 365);
 
 const SANDBOX_CACHE_TIME_TO_LIVE: Duration = ONE_HOUR;
 
+const HOSTED_DOCS_TIME_TO_LIVE: Duration = ONE_HOUR;
+const HOSTED_DOCS_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+const HOSTED_DOCS_PATH: &str = "/docs";
+
+const DEFAULT_BENCHMARK_SAMPLES: u32 = 3;
+const MAX_BENCHMARK_SAMPLES: u32 = 10;
+
 fn main() {
     // Dotenv may be unable to load environment variables, but that's ok in production
     let _ = dotenv::dotenv();
@@ -48,6 +64,7 @@ struct Config {
     address: String,
     cors_enabled: bool,
     gh_token: String,
+    limits: limits::Limits,
     logfile: String,
     metrics_token: Option<String>,
     port: u16,
@@ -75,6 +92,19 @@ impl Config {
             env::var("PLAYGROUND_LOG_FILE").unwrap_or_else(|_| DEFAULT_LOG_FILE.to_string());
         let cors_enabled = env::var_os("PLAYGROUND_CORS_ENABLED").is_some();
 
+        let request_size = env::var("PLAYGROUND_MAX_REQUEST_SIZE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(limits::DEFAULT_REQUEST_SIZE);
+        let code_size = env::var("PLAYGROUND_MAX_CODE_SIZE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(limits::DEFAULT_CODE_SIZE);
+        let limits = limits::Limits {
+            request_size,
+            code_size,
+        };
+
         let axum_enabled = env::var_os("PLAYGROUND_SERVER_AXUM").is_some();
 
         Self {
@@ -82,6 +112,7 @@ impl Config {
             axum_enabled,
             cors_enabled,
             gh_token,
+            limits,
             logfile,
             metrics_token,
             port,
@@ -113,6 +144,10 @@ impl Config {
         GhToken(self.gh_token.clone().into())
     }
 
+    fn limits(&self) -> limits::Limits {
+        self.limits
+    }
+
     fn server_socket_addr(&self) -> SocketAddr {
         let address = self.address.parse().expect("Invalid address");
         SocketAddr::new(address, self.port)
@@ -144,7 +179,7 @@ mod metrics {
     use regex::Regex;
     use std::{future::Future, time::Instant};
 
-    use crate::sandbox::{self, Channel, CompileTarget, CrateType, Edition, Mode};
+    use crate::sandbox::{self, Channel, CompileTarget, CrateType, Edition, Mode, ProfileOptions};
 
     lazy_static! {
         pub(crate) static ref REQUESTS: HistogramVec = register_histogram_vec!(
@@ -163,8 +198,12 @@ mod metrics {
         Format,
         Miri,
         Clippy,
+        Doc,
         MacroExpansion,
+        Benchmark,
         MetaCrates,
+        MetaCrateDependencies,
+        MetaCrateDependents,
         MetaVersionStable,
         MetaVersionBeta,
         MetaVersionNightly,
@@ -195,10 +234,11 @@ mod metrics {
         crate_type: Option<CrateType>,
         tests: Option<bool>,
         backtrace: Option<bool>,
+        profile: Option<ProfileOptions>,
     }
 
     impl Labels {
-        const COUNT: usize = 9;
+        const COUNT: usize = 11;
 
         const LABELS: &'static [&'static str; Self::COUNT] = &[
             "endpoint",
@@ -210,6 +250,8 @@ mod metrics {
             "crate_type",
             "tests",
             "backtrace",
+            "opt_level",
+            "debug",
         ];
 
         fn to_values(&self) -> [&'static str; Self::COUNT] {
@@ -223,6 +265,7 @@ mod metrics {
                 crate_type,
                 tests,
                 backtrace,
+                profile,
             } = *self;
 
             fn b(v: Option<bool>) -> &'static str {
@@ -241,6 +284,17 @@ mod metrics {
             let tests = b(tests);
             let backtrace = b(backtrace);
 
+            fn p<T: Into<&'static str>>(v: Option<Option<T>>) -> &'static str {
+                match v {
+                    None => "",
+                    Some(None) => "Unspecified",
+                    Some(Some(v)) => v.into(),
+                }
+            }
+
+            let opt_level = p(profile.map(|p| p.opt_level));
+            let debug = p(profile.map(|p| p.debug));
+
             [
                 endpoint.into(),
                 outcome.into(),
@@ -251,6 +305,8 @@ mod metrics {
                 crate_type,
                 tests,
                 backtrace,
+                opt_level,
+                debug,
             ]
         }
     }
@@ -278,7 +334,14 @@ mod metrics {
                 edition,
                 tests,
                 backtrace,
+                profile,
+                functions: _,
+                mir: _,
+                wasm: _,
+                size_analysis: _,
                 code: _,
+                proc_macro: _,
+                manifest: _,
             } = *self;
 
             Labels {
@@ -292,6 +355,7 @@ mod metrics {
                 crate_type: Some(crate_type),
                 tests: Some(tests),
                 backtrace: Some(backtrace),
+                profile: Some(profile),
             }
         }
     }
@@ -304,8 +368,13 @@ mod metrics {
                 edition,
                 crate_type,
                 tests,
+                doctests: _,
+                test_filter: _,
                 backtrace,
+                profile,
                 code: _,
+                proc_macro: _,
+                manifest: _,
             } = *self;
 
             Labels {
@@ -319,6 +388,7 @@ mod metrics {
                 crate_type: Some(crate_type),
                 tests: Some(tests),
                 backtrace: Some(backtrace),
+                profile: Some(profile),
             }
         }
     }
@@ -338,6 +408,7 @@ mod metrics {
                 crate_type: None,
                 tests: None,
                 backtrace: None,
+                profile: None,
             }
         }
     }
@@ -361,6 +432,7 @@ mod metrics {
                 crate_type: Some(crate_type),
                 tests: None,
                 backtrace: None,
+                profile: None,
             }
         }
     }
@@ -380,25 +452,85 @@ mod metrics {
                 crate_type: None,
                 tests: None,
                 backtrace: None,
+                profile: None,
+            }
+        }
+    }
+
+    impl GenerateLabels for sandbox::DocRequest {
+        fn generate_labels(&self, outcome: Outcome) -> Labels {
+            let Self {
+                channel,
+                edition,
+                crate_type,
+                format: _,
+                code: _,
+            } = *self;
+
+            Labels {
+                endpoint: Endpoint::Doc,
+                outcome,
+
+                target: None,
+                channel: Some(channel),
+                mode: None,
+                edition: Some(edition),
+                crate_type: Some(crate_type),
+                tests: None,
+                backtrace: None,
+                profile: None,
             }
         }
     }
 
     impl GenerateLabels for sandbox::MacroExpansionRequest {
         fn generate_labels(&self, outcome: Outcome) -> Labels {
-            let Self { code: _, edition } = *self;
+            let Self {
+                channel,
+                code: _,
+                edition,
+                proc_macro: _,
+                item: _,
+                format: _,
+            } = *self;
 
             Labels {
                 endpoint: Endpoint::MacroExpansion,
                 outcome,
 
                 target: None,
-                channel: None,
+                channel: Some(channel),
                 mode: None,
                 edition: Some(edition),
                 crate_type: None,
                 tests: None,
                 backtrace: None,
+                profile: None,
+            }
+        }
+    }
+
+    impl GenerateLabels for sandbox::BenchmarkRequest {
+        fn generate_labels(&self, outcome: Outcome) -> Labels {
+            let Self {
+                code: _,
+                edition,
+                crate_type,
+                samples: _,
+            } = *self;
+
+            Labels {
+                endpoint: Endpoint::Benchmark,
+                outcome,
+
+                target: None,
+                channel: Some(Channel::Nightly),
+                mode: Some(Mode::Release),
+                edition: Some(edition),
+                crate_type: Some(crate_type),
+                tests: None,
+                backtrace: None,
+                profile: None,
             }
         }
     }
@@ -469,12 +601,24 @@ mod metrics {
         }
     }
 
+    impl SuccessDetails for sandbox::DocResponse {
+        fn success_details(&self) -> Outcome {
+            common_success_details(self.success, &self.stderr)
+        }
+    }
+
     impl SuccessDetails for sandbox::MacroExpansionResponse {
         fn success_details(&self) -> Outcome {
             common_success_details(self.success, &self.stderr)
         }
     }
 
+    impl SuccessDetails for sandbox::BenchmarkResponse {
+        fn success_details(&self) -> Outcome {
+            common_success_details(self.success, &self.stderr)
+        }
+    }
+
     impl SuccessDetails for Vec<sandbox::CrateInformation> {
         fn success_details(&self) -> Outcome {
             Outcome::Success
@@ -610,6 +754,7 @@ mod metrics {
             crate_type: None,
             tests: None,
             backtrace: None,
+            profile: None,
         };
         let values = &labels.to_values();
         let histogram = REQUESTS.with_label_values(values);
@@ -646,6 +791,7 @@ mod metrics {
             crate_type: None,
             tests: None,
             backtrace: None,
+            profile: None,
         };
         let values = &labels.to_values();
         let histogram = REQUESTS.with_label_values(values);
@@ -674,6 +820,12 @@ pub enum Error {
     Formatting { source: sandbox::Error },
     #[snafu(display("Interpreting operation failed: {}", source))]
     Interpreting { source: sandbox::Error },
+    #[snafu(display("Benchmarking operation failed: {}", source))]
+    Benchmarking { source: sandbox::Error },
+    #[snafu(display("Documentation operation failed: {}", source))]
+    Documenting { source: sandbox::Error },
+    #[snafu(display("Unable to host the generated documentation: {}", source))]
+    DocHosting { source: std::io::Error },
     #[snafu(display("Caching operation failed: {}", source))]
     Caching { source: sandbox::Error },
     #[snafu(display("Gist creation failed: {}", source))]
@@ -684,6 +836,8 @@ pub enum Error {
     Serialization { source: serde_json::Error },
     #[snafu(display("Unable to deserialize request: {}", source))]
     Deserialization { source: bodyparser::BodyError },
+    #[snafu(display("The request was rejected: {}", source))]
+    RequestRejected { source: limits::Error },
     #[snafu(display("The value {:?} is not a valid target", value))]
     InvalidTarget { value: String },
     #[snafu(display("The value {:?} is not a valid assembly flavor", value))]
@@ -692,10 +846,53 @@ pub enum Error {
     InvalidDemangleAssembly { value: String },
     #[snafu(display("The value {:?} is not a valid assembly processing option", value))]
     InvalidProcessAssembly { value: String },
+    #[snafu(display("The value {:?} is not a valid MIR phase", value))]
+    InvalidMirPhase { value: String },
+    #[snafu(display("Dumping MIR from other phases or passes requires the nightly channel"))]
+    MirDumpRequiresNightly,
+    #[snafu(display(
+        "The value {} is not a valid number of benchmark samples; it must be between 1 and {}",
+        value,
+        MAX_BENCHMARK_SAMPLES,
+    ))]
+    InvalidBenchmarkSamples { value: u32 },
+    #[snafu(display("The value {:?} is not a valid documentation format", value))]
+    InvalidDocFormat { value: String },
+    #[snafu(display("Documentation can only be generated for library crates"))]
+    DocRequiresLibrary,
+    #[snafu(display("JSON documentation requires the nightly channel"))]
+    DocJsonRequiresNightly,
+    #[snafu(display("The value {:?} is not a valid WebAssembly target", value))]
+    InvalidWasmTarget { value: String },
+    #[snafu(display("Executing WebAssembly requires the wasm32-wasi target"))]
+    WasmExecuteRequiresWasi,
+    #[snafu(display("wasm-bindgen requires the wasm32-unknown-unknown target"))]
+    WasmBindgenRequiresUnknown,
+    #[snafu(display("A test filter can only be used when running tests"))]
+    TestFilterRequiresTests,
+    #[snafu(display("Doctests can only be run when testing a library crate"))]
+    DoctestsRequireLibraryTests,
+    #[snafu(display("Code using a procedural macro crate must be a binary crate"))]
+    ProcMacroRequiresBinary,
+    #[snafu(display("The manifest is not allowed: {}", source))]
+    InvalidManifest { source: manifest::Error },
+    #[snafu(display("The crate {:?} is not one of the available crates", name))]
+    CrateNotFound { name: String },
     #[snafu(display("The value {:?} is not a valid channel", value,))]
     InvalidChannel { value: String },
     #[snafu(display("The value {:?} is not a valid mode", value))]
     InvalidMode { value: String },
+    #[snafu(display("The value {:?} is not a valid optimization level", value))]
+    InvalidOptLevel { value: String },
+    #[snafu(display("The value {:?} is not a valid debug info level", value))]
+    InvalidDebugInfo { value: String },
+    #[snafu(display(
+        "The `{}` profile setting would rebuild every available crate, so it cannot be changed",
+        setting
+    ))]
+    ProfileSettingNotAllowed { setting: &'static str },
+    #[snafu(display("The number of codegen units must be at least 1"))]
+    InvalidCodegenUnits,
     #[snafu(display("The value {:?} is not a valid edition", value))]
     InvalidEdition { value: String },
     #[snafu(display("The value {:?} is not a valid crate type", value))]
@@ -708,12 +905,204 @@ pub enum Error {
 
 type Result<T, E = Error> = ::std::result::Result<T, E>;
 
+impl Error {
+    /// The HTTP status code for this error. Mistakes in the request
+    /// are 4xx; failures of the playground itself are 5xx.
+    fn status_code(&self) -> u16 {
+        use self::Error::*;
+
+        match self {
+            SandboxCreation { source }
+            | Compilation { source }
+            | Execution { source }
+            | Evaluation { source }
+            | Linting { source }
+            | Expansion { source }
+            | Formatting { source }
+            | Interpreting { source }
+            | Benchmarking { source }
+            | Documenting { source }
+            | Caching { source } => sandbox_status_code(source),
+
+            DocHosting { .. } | Serialization { .. } | CachePoisoned => 500,
+
+            GistCreation { .. } | GistLoading { .. } => 502,
+
+            RequestRejected { source } => source.status_code(),
+
+            CrateNotFound { .. } => 404,
+
+            Deserialization { .. }
+            | RequestMissing
+            | InvalidTarget { .. }
+            | InvalidAssemblyFlavor { .. }
+            | InvalidDemangleAssembly { .. }
+            | InvalidProcessAssembly { .. }
+            | InvalidMirPhase { .. }
+            | MirDumpRequiresNightly
+            | InvalidBenchmarkSamples { .. }
+            | InvalidDocFormat { .. }
+            | DocRequiresLibrary
+            | DocJsonRequiresNightly
+            | InvalidWasmTarget { .. }
+            | WasmExecuteRequiresWasi
+            | WasmBindgenRequiresUnknown
+            | TestFilterRequiresTests
+            | DoctestsRequireLibraryTests
+            | ProcMacroRequiresBinary
+            | InvalidManifest { .. }
+            | InvalidChannel { .. }
+            | InvalidMode { .. }
+            | InvalidOptLevel { .. }
+            | InvalidDebugInfo { .. }
+            | ProfileSettingNotAllowed { .. }
+            | InvalidCodegenUnits
+            | InvalidEdition { .. }
+            | InvalidCrateType { .. } => 400,
+        }
+    }
+
+    /// A stable, machine-readable name for this error. When the
+    /// sandbox or the request limits caused the error, their more
+    /// specific name is used instead. These are part of the API, so
+    /// an existing code must not change.
+    fn code(&self) -> &'static str {
+        use self::Error::*;
+
+        match self {
+            SandboxCreation { source }
+            | Compilation { source }
+            | Execution { source }
+            | Evaluation { source }
+            | Linting { source }
+            | Expansion { source }
+            | Formatting { source }
+            | Interpreting { source }
+            | Benchmarking { source }
+            | Documenting { source }
+            | Caching { source } => sandbox_code(source),
+            RequestRejected { source } => source.code(),
+
+            DocHosting { .. } => "DocHosting",
+            GistCreation { .. } => "GistCreation",
+            GistLoading { .. } => "GistLoading",
+            Serialization { .. } => "Serialization",
+            Deserialization { .. } => DESERIALIZATION_ERROR_CODE,
+            InvalidTarget { .. } => "InvalidTarget",
+            InvalidAssemblyFlavor { .. } => "InvalidAssemblyFlavor",
+            InvalidDemangleAssembly { .. } => "InvalidDemangleAssembly",
+            InvalidProcessAssembly { .. } => "InvalidProcessAssembly",
+            InvalidMirPhase { .. } => "InvalidMirPhase",
+            MirDumpRequiresNightly => "MirDumpRequiresNightly",
+            InvalidBenchmarkSamples { .. } => "InvalidBenchmarkSamples",
+            InvalidDocFormat { .. } => "InvalidDocFormat",
+            DocRequiresLibrary => "DocRequiresLibrary",
+            DocJsonRequiresNightly => "DocJsonRequiresNightly",
+            InvalidWasmTarget { .. } => "InvalidWasmTarget",
+            WasmExecuteRequiresWasi => "WasmExecuteRequiresWasi",
+            WasmBindgenRequiresUnknown => "WasmBindgenRequiresUnknown",
+            TestFilterRequiresTests => "TestFilterRequiresTests",
+            DoctestsRequireLibraryTests => "DoctestsRequireLibraryTests",
+            ProcMacroRequiresBinary => "ProcMacroRequiresBinary",
+            InvalidManifest { .. } => "InvalidManifest",
+            CrateNotFound { .. } => "CrateNotFound",
+            InvalidChannel { .. } => "InvalidChannel",
+            InvalidMode { .. } => "InvalidMode",
+            InvalidOptLevel { .. } => "InvalidOptLevel",
+            InvalidDebugInfo { .. } => "InvalidDebugInfo",
+            ProfileSettingNotAllowed { .. } => "ProfileSettingNotAllowed",
+            InvalidCodegenUnits => "InvalidCodegenUnits",
+            InvalidEdition { .. } => "InvalidEdition",
+            InvalidCrateType { .. } => "InvalidCrateType",
+            RequestMissing => "RequestMissing",
+            CachePoisoned => "CachePoisoned",
+        }
+    }
+}
+
+fn sandbox_code(error: &sandbox::Error) -> &'static str {
+    use sandbox::Error::*;
+
+    match error {
+        UnableToCreateTempDir { .. } => "UnableToCreateTempDir",
+        UnableToCreateOutputDir { .. } => "UnableToCreateOutputDir",
+        UnableToSetOutputPermissions { .. } => "UnableToSetOutputPermissions",
+        UnableToCreateSourceFile { .. } => "UnableToCreateSourceFile",
+        UnableToSetSourcePermissions { .. } => "UnableToSetSourcePermissions",
+        UnableToSerializeJobSpec { .. } => "UnableToSerializeJobSpec",
+        UnableToCreateJobSpec { .. } => "UnableToCreateJobSpec",
+        JobSpecContainsNul => "JobSpecContainsNul",
+        UnableToStartCompiler { .. } => "UnableToStartCompiler",
+        MissingCompilerId => "MissingCompilerId",
+        UnableToWaitForCompiler { .. } => "UnableToWaitForCompiler",
+        UnableToGetOutputFromCompiler { .. } => "UnableToGetOutputFromCompiler",
+        UnableToRemoveCompiler { .. } => "UnableToRemoveCompiler",
+        CompilerExecutionTimedOut { .. } => "CompilerExecutionTimedOut",
+        UnableToReadOutput { .. } => "UnableToReadOutput",
+        UnableToParseCrateInformation { .. } => "UnableToParseCrateInformation",
+        UnableToParseDependencyGraph { .. } => "UnableToParseDependencyGraph",
+        OutputNotUtf8 { .. } => "OutputNotUtf8",
+        OutputMissing => "OutputMissing",
+        VersionReleaseMissing => "VersionReleaseMissing",
+        VersionHashMissing => "VersionHashMissing",
+        VersionDateMissing => "VersionDateMissing",
+    }
+}
+
+fn sandbox_status_code(error: &sandbox::Error) -> u16 {
+    use sandbox::Error::*;
+
+    match error {
+        CompilerExecutionTimedOut { .. } => 504,
+
+        // The containers could not be run
+        UnableToStartCompiler { .. }
+        | MissingCompilerId
+        | UnableToWaitForCompiler { .. }
+        | UnableToGetOutputFromCompiler { .. }
+        | UnableToRemoveCompiler { .. } => 503,
+
+        // The containers ran but did not produce what we expected
+        UnableToReadOutput { .. }
+        | UnableToParseCrateInformation { .. }
+        | UnableToParseDependencyGraph { .. }
+        | OutputNotUtf8 { .. }
+        | OutputMissing
+        | VersionReleaseMissing
+        | VersionHashMissing
+        | VersionDateMissing => 502,
+
+        UnableToCreateTempDir { .. }
+        | UnableToCreateOutputDir { .. }
+        | UnableToSetOutputPermissions { .. }
+        | UnableToCreateSourceFile { .. }
+        | UnableToSetSourcePermissions { .. }
+        | UnableToSerializeJobSpec { .. }
+        | UnableToCreateJobSpec { .. } => 500,
+
+        JobSpecContainsNul => 400,
+    }
+}
+
 const FATAL_ERROR_JSON: &str =
-    r#"{"error": "Multiple cascading errors occurred, abandon all hope"}"#;
+    r#"{"error": "Multiple cascading errors occurred, abandon all hope", "code": "Fatal"}"#;
+
+/// Reported when the request body cannot be read or deserialized.
+const DESERIALIZATION_ERROR_CODE: &str = "Deserialization";
 
 #[derive(Debug, Clone, Serialize)]
 struct ErrorJson {
     error: String,
+    code: &'static str,
+}
+
+impl From<Error> for ErrorJson {
+    fn from(e: Error) -> Self {
+        Self {
+            error: e.to_string(),
+            code: e.code(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -725,6 +1114,22 @@ struct CompileRequest {
     demangle_assembly: Option<String>,
     #[serde(rename = "processAssembly")]
     process_assembly: Option<String>,
+    #[serde(rename = "mirPhase")]
+    mir_phase: Option<String>,
+    #[serde(rename = "mirDump")]
+    mir_dump: Option<String>,
+    #[serde(default, rename = "mirGraphviz")]
+    mir_graphviz: bool,
+    #[serde(rename = "wasmTarget")]
+    wasm_target: Option<String>,
+    #[serde(default, rename = "wasmBinary")]
+    wasm_binary: bool,
+    #[serde(default, rename = "wasmBindgen")]
+    wasm_bindgen: bool,
+    #[serde(default, rename = "wasmExecute")]
+    wasm_execute: bool,
+    #[serde(rename = "symbolLimit")]
+    symbol_limit: Option<usize>,
     channel: String,
     mode: String,
     #[serde(default)]
@@ -734,17 +1139,90 @@ struct CompileRequest {
     tests: bool,
     #[serde(default)]
     backtrace: bool,
+    #[serde(default)]
+    functions: Vec<String>,
+    #[serde(flatten)]
+    profile: ProfileSettings,
     code: String,
+    #[serde(rename = "procMacro")]
+    proc_macro: Option<String>,
+    manifest: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
 struct CompileResponse {
     success: bool,
     code: String,
+    #[serde(rename = "sourceMap")]
+    source_map: Vec<SourceMapping>,
+    functions: Vec<FunctionLocation>,
+    #[serde(rename = "mirDumps")]
+    mir_dumps: Vec<MirDump>,
+    #[serde(rename = "mirGraphviz")]
+    mir_graphviz: Option<String>,
+    #[serde(rename = "wasmBinary")]
+    wasm_binary: Option<String>,
+    #[serde(rename = "wasmJs")]
+    wasm_js: Option<String>,
+    #[serde(rename = "sizeAnalysis")]
+    size_analysis: Option<SizeAnalysis>,
     stdout: String,
     stderr: String,
 }
 
+#[derive(Debug, Clone, Serialize)]
+struct LineRange {
+    start: u32,
+    end: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SourceMapping {
+    source: LineRange,
+    code: LineRange,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct FunctionLocation {
+    name: String,
+    lines: Option<LineRange>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SizeAnalysis {
+    sections: Vec<SectionSize>,
+    symbols: Vec<SymbolSize>,
+    crates: Vec<CrateSize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SectionSize {
+    name: String,
+    size: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SymbolSize {
+    name: String,
+    #[serde(rename = "crateName")]
+    crate_name: String,
+    size: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CrateSize {
+    name: String,
+    size: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MirDump {
+    item: String,
+    pass: String,
+    mir: String,
+    graphviz: Option<String>,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 struct ExecuteRequest {
     channel: String,
@@ -755,8 +1233,36 @@ struct ExecuteRequest {
     crate_type: String,
     tests: bool,
     #[serde(default)]
+    doctests: bool,
+    #[serde(rename = "testFilter")]
+    test_filter: Option<String>,
+    #[serde(default)]
     backtrace: bool,
+    #[serde(flatten)]
+    profile: ProfileSettings,
     code: String,
+    #[serde(rename = "procMacro")]
+    proc_macro: Option<String>,
+    manifest: Option<String>,
+}
+
+/// Overrides for the profile selected by `mode`, shared by the
+/// requests that build the code
+#[derive(Debug, Clone, Default, Deserialize)]
+struct ProfileSettings {
+    #[serde(rename = "optLevel")]
+    opt_level: Option<String>,
+    debug: Option<String>,
+    /// Rejected, as Cargo only accepts it for the whole profile
+    panic: Option<String>,
+    #[serde(rename = "overflowChecks")]
+    overflow_checks: Option<bool>,
+    #[serde(rename = "debugAssertions")]
+    debug_assertions: Option<bool>,
+    #[serde(rename = "codegenUnits")]
+    codegen_units: Option<u32>,
+    /// Rejected, as Cargo only accepts it for the whole profile
+    lto: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -764,6 +1270,40 @@ struct ExecuteResponse {
     success: bool,
     stdout: String,
     stderr: String,
+    #[serde(rename = "testResults")]
+    test_results: Option<TestResults>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct TestResults {
+    tests: Vec<TestCase>,
+    summary: TestSummary,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct TestCase {
+    name: String,
+    status: &'static str,
+    duration: Option<f64>,
+    output: Option<String>,
+    #[serde(rename = "docItem")]
+    doc_item: Option<DocItem>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct DocItem {
+    name: String,
+    line: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct TestSummary {
+    passed: u32,
+    failed: u32,
+    ignored: u32,
+    measured: u32,
+    #[serde(rename = "filteredOut")]
+    filtered_out: u32,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -797,6 +1337,29 @@ struct ClippyResponse {
     stderr: String,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+struct DocRequest {
+    channel: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(rename = "crateType")]
+    crate_type: String,
+    format: Option<String>,
+    code: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct DocResponse {
+    success: bool,
+    /// Where the generated HTML can be viewed until it expires
+    url: Option<String>,
+    /// The generated HTML as a base64-encoded, gzipped tarball
+    archive: Option<String>,
+    json: Option<String>,
+    stdout: String,
+    stderr: String,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 struct MiriRequest {
     code: String,
@@ -813,9 +1376,15 @@ struct MiriResponse {
 
 #[derive(Debug, Clone, Deserialize)]
 struct MacroExpansionRequest {
+    channel: Option<String>,
     code: String,
     #[serde(default)]
     edition: String,
+    #[serde(rename = "procMacro")]
+    proc_macro: Option<String>,
+    item: Option<String>,
+    #[serde(default)]
+    format: bool,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -825,11 +1394,55 @@ struct MacroExpansionResponse {
     stderr: String,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+struct BenchmarkRequest {
+    code: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(default = "default_crate_type", rename = "crateType")]
+    crate_type: String,
+    samples: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct BenchmarkResponse {
+    success: bool,
+    benchmarks: Vec<Benchmark>,
+    stdout: String,
+    stderr: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Benchmark {
+    name: String,
+    mean: f64,
+    median: f64,
+    #[serde(rename = "stdDev")]
+    std_dev: f64,
+    min: f64,
+    max: f64,
+    samples: usize,
+    /// libtest doesn't report how many iterations it ran for each
+    /// sample, so this is always null
+    iterations: Option<u64>,
+    #[serde(rename = "mibPerSecond")]
+    mib_per_second: Option<f64>,
+}
+
 #[derive(Debug, Clone, Serialize)]
 struct CrateInformation {
     name: String,
     version: String,
     id: String,
+    description: Option<String>,
+    documentation: Option<String>,
+    repository: Option<String>,
+    license: Option<String>,
+    features: Vec<String>,
+    #[serde(rename = "availableFeatures")]
+    available_features: Vec<String>,
+    sources: Vec<&'static str>,
+    aliases: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -837,6 +1450,32 @@ struct MetaCratesResponse {
     crates: Vec<CrateInformation>,
 }
 
+#[derive(Debug, Clone, Serialize)]
+struct CrateNode {
+    id: String,
+    name: String,
+    version: String,
+    features: Vec<String>,
+    dependencies: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MetaCrateDependenciesResponse {
+    roots: Vec<String>,
+    crates: Vec<CrateNode>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CrateDependents {
+    id: String,
+    paths: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MetaCrateDependentsResponse {
+    crates: Vec<CrateDependents>,
+}
+
 #[derive(Debug, Clone, Serialize)]
 struct MetaVersionResponse {
     version: String,
@@ -903,15 +1542,64 @@ impl TryFrom<CompileRequest> for sandbox::CompileRequest {
             _ => target,
         };
 
+        let mir_phase = match me.mir_phase {
+            Some(p) => parse_mir_phase(&p)?,
+            None => sandbox::MirPhase::Optimized,
+        };
+        let mir = sandbox::MirOptions {
+            phase: mir_phase,
+            dump: me.mir_dump,
+            graphviz: me.mir_graphviz,
+        };
+
+        let channel = parse_channel(&me.channel)?;
+        if target == sandbox::CompileTarget::Mir
+            && mir.requires_nightly()
+            && channel != sandbox::Channel::Nightly
+        {
+            return MirDumpRequiresNightlySnafu.fail();
+        }
+
+        let wasm_target = match me.wasm_target {
+            Some(t) => parse_wasm_target(&t)?,
+            None => sandbox::WasmTarget::Unknown,
+        };
+
+        if me.wasm_execute && wasm_target != sandbox::WasmTarget::Wasi {
+            return WasmExecuteRequiresWasiSnafu.fail();
+        }
+        if me.wasm_bindgen && wasm_target != sandbox::WasmTarget::Unknown {
+            return WasmBindgenRequiresUnknownSnafu.fail();
+        }
+
+        let crate_type = parse_crate_type(&me.crate_type)?;
+        check_proc_macro_consumer(&me.proc_macro, crate_type)?;
+        check_manifest(&me.manifest)?;
+
         Ok(sandbox::CompileRequest {
             target,
-            channel: parse_channel(&me.channel)?,
+            channel,
             mode: parse_mode(&me.mode)?,
             edition: parse_edition(&me.edition)?,
-            crate_type: parse_crate_type(&me.crate_type)?,
+            crate_type,
             tests: me.tests,
             backtrace: me.backtrace,
+            functions: me.functions,
+            mir,
+            wasm: sandbox::WasmOptions {
+                target: wasm_target,
+                binary: me.wasm_binary,
+                bindgen: me.wasm_bindgen,
+                execute: me.wasm_execute,
+            },
+            size_analysis: match me.symbol_limit {
+                Some(symbol_limit) => sandbox::SizeAnalysisOptions { symbol_limit },
+                None => sandbox::SizeAnalysisOptions::default(),
+            },
+            profile: sandbox::ProfileOptions::try_from(me.profile)?,
             code: me.code,
+            proc_macro: me.proc_macro,
+            manifest: me.manifest,
         })
     }
 }
@@ -921,24 +1609,142 @@ impl From<sandbox::CompileResponse> for CompileResponse {
         CompileResponse {
             success: me.success,
             code: me.code,
+            source_map: me.source_map.into_iter().map(Into::into).collect(),
+            functions: me.functions.into_iter().map(Into::into).collect(),
+            mir_dumps: me.mir_dumps.into_iter().map(Into::into).collect(),
+            mir_graphviz: me.mir_graphviz,
+            wasm_binary: me.wasm_binary,
+            wasm_js: me.wasm_js,
+            size_analysis: me.size_analysis.map(Into::into),
             stdout: me.stdout,
             stderr: me.stderr,
         }
     }
 }
 
+impl From<sandbox::LineRange> for LineRange {
+    fn from(me: sandbox::LineRange) -> Self {
+        LineRange {
+            start: me.start,
+            end: me.end,
+        }
+    }
+}
+
+impl From<sandbox::SourceMapping> for SourceMapping {
+    fn from(me: sandbox::SourceMapping) -> Self {
+        SourceMapping {
+            source: me.source.into(),
+            code: me.code.into(),
+        }
+    }
+}
+
+impl From<sandbox::FunctionLocation> for FunctionLocation {
+    fn from(me: sandbox::FunctionLocation) -> Self {
+        FunctionLocation {
+            name: me.name,
+            lines: me.lines.map(Into::into),
+        }
+    }
+}
+
+impl From<sandbox::SizeAnalysis> for SizeAnalysis {
+    fn from(me: sandbox::SizeAnalysis) -> Self {
+        SizeAnalysis {
+            sections: me
+                .sections
+                .into_iter()
+                .map(|s| SectionSize {
+                    name: s.name,
+                    size: s.size,
+                })
+                .collect(),
+            symbols: me
+                .symbols
+                .into_iter()
+                .map(|s| SymbolSize {
+                    name: s.name,
+                    crate_name: s.crate_name,
+                    size: s.size,
+                })
+                .collect(),
+            crates: me
+                .crates
+                .into_iter()
+                .map(|c| CrateSize {
+                    name: c.name,
+                    size: c.size,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<sandbox::MirDump> for MirDump {
+    fn from(me: sandbox::MirDump) -> Self {
+        MirDump {
+            item: me.item,
+            pass: me.pass,
+            mir: me.mir,
+            graphviz: me.graphviz,
+        }
+    }
+}
+
 impl TryFrom<ExecuteRequest> for sandbox::ExecuteRequest {
     type Error = Error;
 
     fn try_from(me: ExecuteRequest) -> Result<Self> {
+        if me.test_filter.is_some() && !me.tests {
+            return TestFilterRequiresTestsSnafu.fail();
+        }
+
+        let crate_type = parse_crate_type(&me.crate_type)?;
+        let is_library = matches!(crate_type, sandbox::CrateType::Library(_));
+        if me.doctests && !(me.tests && is_library) {
+            return DoctestsRequireLibraryTestsSnafu.fail();
+        }
+        check_proc_macro_consumer(&me.proc_macro, crate_type)?;
+        check_manifest(&me.manifest)?;
+
         Ok(sandbox::ExecuteRequest {
             channel: parse_channel(&me.channel)?,
             mode: parse_mode(&me.mode)?,
             edition: parse_edition(&me.edition)?,
-            crate_type: parse_crate_type(&me.crate_type)?,
+            crate_type,
             tests: me.tests,
+            doctests: me.doctests,
+            test_filter: me.test_filter,
             backtrace: me.backtrace,
+            profile: sandbox::ProfileOptions::try_from(me.profile)?,
             code: me.code,
+            proc_macro: me.proc_macro,
+            manifest: me.manifest,
+        })
+    }
+}
+
+impl TryFrom<ProfileSettings> for sandbox::ProfileOptions {
+    type Error = Error;
+
+    fn try_from(me: ProfileSettings) -> Result<Self> {
+        if me.codegen_units == Some(0) {
+            return InvalidCodegenUnitsSnafu.fail();
+        }
+        if me.panic.is_some() {
+            return ProfileSettingNotAllowedSnafu { setting: "panic" }.fail();
+        }
+        if me.lto.is_some() {
+            return ProfileSettingNotAllowedSnafu { setting: "lto" }.fail();
+        }
+
+        Ok(sandbox::ProfileOptions {
+            opt_level: me.opt_level.as_deref().map(parse_opt_level).transpose()?,
+            debug: me.debug.as_deref().map(parse_debug_info).transpose()?,
+            overflow_checks: me.overflow_checks,
+            debug_assertions: me.debug_assertions,
+            codegen_units: me.codegen_units,
         })
     }
 }
@@ -949,6 +1755,39 @@ impl From<sandbox::ExecuteResponse> for ExecuteResponse {
             success: me.success,
             stdout: me.stdout,
             stderr: me.stderr,
+            test_results: me.test_results.map(TestResults::from),
+        }
+    }
+}
+
+impl From<sandbox::TestResults> for TestResults {
+    fn from(me: sandbox::TestResults) -> Self {
+        TestResults {
+            tests: me
+                .tests
+                .into_iter()
+                .map(|t| TestCase {
+                    name: t.name,
+                    status: match t.status {
+                        sandbox::TestStatus::Passed => "passed",
+                        sandbox::TestStatus::Failed => "failed",
+                        sandbox::TestStatus::Ignored => "ignored",
+                    },
+                    duration: t.duration,
+                    output: t.output,
+                    doc_item: t.doc_item.map(|i| DocItem {
+                        name: i.name,
+                        line: i.line,
+                    }),
+                })
+                .collect(),
+            summary: TestSummary {
+                passed: me.summary.passed,
+                failed: me.summary.failed,
+                ignored: me.summary.ignored,
+                measured: me.summary.measured,
+                filtered_out: me.summary.filtered_out,
+            },
         }
     }
 }
@@ -1018,13 +1857,72 @@ impl From<sandbox::MiriResponse> for MiriResponse {
     }
 }
 
+impl TryFrom<DocRequest> for sandbox::DocRequest {
+    type Error = Error;
+
+    fn try_from(me: DocRequest) -> Result<Self> {
+        let channel = parse_channel(&me.channel)?;
+        let crate_type = parse_crate_type(&me.crate_type)?;
+        let format = match me.format {
+            Some(f) => parse_doc_format(&f)?,
+            None => sandbox::DocFormat::Html,
+        };
+
+        if !matches!(crate_type, sandbox::CrateType::Library(_)) {
+            return DocRequiresLibrarySnafu.fail();
+        }
+        if format == sandbox::DocFormat::Json && channel != sandbox::Channel::Nightly {
+            return DocJsonRequiresNightlySnafu.fail();
+        }
+
+        Ok(sandbox::DocRequest {
+            channel,
+            edition: parse_edition(&me.edition)?,
+            crate_type,
+            format,
+            code: me.code,
+        })
+    }
+}
+
+impl DocResponse {
+    /// Makes the generated HTML available from the server
+    fn hosted(me: sandbox::DocResponse, docs: &hosted_docs::HostedDocs) -> Result<Self> {
+        let url = match &me.archive {
+            Some(archive) => {
+                let id = docs.host(archive).context(DocHostingSnafu)?;
+                Some(format!("{}/{}/playground/index.html", HOSTED_DOCS_PATH, id))
+            }
+            None => None,
+        };
+
+        Ok(DocResponse {
+            success: me.success,
+            url,
+            archive: me.archive.map(base64::encode),
+            json: me.json,
+            stdout: me.stdout,
+            stderr: me.stderr,
+        })
+    }
+}
+
 impl TryFrom<MacroExpansionRequest> for sandbox::MacroExpansionRequest {
     type Error = Error;
 
     fn try_from(me: MacroExpansionRequest) -> Result<Self> {
+        let channel = match me.channel {
+            Some(c) => parse_channel(&c)?,
+            None => sandbox::Channel::Nightly,
+        };
+
         Ok(sandbox::MacroExpansionRequest {
+            channel,
             code: me.code,
             edition: parse_edition(&me.edition)?,
+            proc_macro: me.proc_macro,
+            item: me.item,
+            format: me.format,
         })
     }
 }
@@ -1039,6 +1937,49 @@ impl From<sandbox::MacroExpansionResponse> for MacroExpansionResponse {
     }
 }
 
+impl TryFrom<BenchmarkRequest> for sandbox::BenchmarkRequest {
+    type Error = Error;
+
+    fn try_from(me: BenchmarkRequest) -> Result<Self> {
+        let samples = me.samples.unwrap_or(DEFAULT_BENCHMARK_SAMPLES);
+        if samples == 0 || samples > MAX_BENCHMARK_SAMPLES {
+            return InvalidBenchmarkSamplesSnafu { value: samples }.fail();
+        }
+
+        Ok(sandbox::BenchmarkRequest {
+            code: me.code,
+            edition: parse_edition(&me.edition)?,
+            crate_type: parse_crate_type(&me.crate_type)?,
+            samples,
+        })
+    }
+}
+
+impl From<sandbox::BenchmarkResponse> for BenchmarkResponse {
+    fn from(me: sandbox::BenchmarkResponse) -> Self {
+        BenchmarkResponse {
+            success: me.success,
+            benchmarks: me
+                .benchmarks
+                .into_iter()
+                .map(|b| Benchmark {
+                    name: b.name,
+                    mean: b.mean,
+                    median: b.median,
+                    std_dev: b.std_dev,
+                    min: b.min,
+                    max: b.max,
+                    samples: b.samples,
+                    iterations: None,
+                    mib_per_second: b.mib_per_second,
+                })
+                .collect(),
+            stdout: me.stdout,
+            stderr: me.stderr,
+        }
+    }
+}
+
 impl From<Vec<sandbox::CrateInformation>> for MetaCratesResponse {
     fn from(me: Vec<sandbox::CrateInformation>) -> Self {
         let crates = me
@@ -1047,6 +1988,22 @@ impl From<Vec<sandbox::CrateInformation>> for MetaCratesResponse {
                 name: cv.name,
                 version: cv.version,
                 id: cv.id,
+                description: cv.description,
+                documentation: cv.documentation,
+                repository: cv.repository,
+                license: cv.license,
+                features: cv.features,
+                available_features: cv.available_features,
+                sources: cv
+                    .sources
+                    .into_iter()
+                    .map(|s| match s {
+                        sandbox::CrateSource::TopDownloads => "topDownloads",
+                        sandbox::CrateSource::Cookbook => "cookbook",
+                        sandbox::CrateSource::Addition => "addition",
+                    })
+                    .collect(),
+                aliases: cv.aliases,
             })
             .collect();
 
@@ -1054,6 +2011,49 @@ impl From<Vec<sandbox::CrateInformation>> for MetaCratesResponse {
     }
 }
 
+impl MetaCrateDependenciesResponse {
+    fn lookup(graph: &sandbox::DependencyGraph, name: &str) -> Result<Self> {
+        let dependencies = graph
+            .dependencies(name)
+            .context(CrateNotFoundSnafu { name })?;
+
+        let crates = dependencies
+            .packages
+            .into_iter()
+            .map(|p| CrateNode {
+                id: p.id,
+                name: p.name,
+                version: p.version,
+                features: p.features,
+                dependencies: p.dependencies,
+            })
+            .collect();
+
+        Ok(MetaCrateDependenciesResponse {
+            roots: dependencies.roots,
+            crates,
+        })
+    }
+}
+
+impl MetaCrateDependentsResponse {
+    fn lookup(graph: &sandbox::DependencyGraph, name: &str) -> Result<Self> {
+        let dependents = graph
+            .dependents(name)
+            .context(CrateNotFoundSnafu { name })?;
+
+        let crates = dependents
+            .into_iter()
+            .map(|d| CrateDependents {
+                id: d.package,
+                paths: d.paths,
+            })
+            .collect();
+
+        Ok(MetaCrateDependentsResponse { crates })
+    }
+}
+
 impl From<sandbox::Version> for MetaVersionResponse {
     fn from(me: sandbox::Version) -> Self {
         MetaVersionResponse {
@@ -1088,8 +2088,13 @@ impl TryFrom<EvaluateRequest> for sandbox::ExecuteRequest {
             edition: parse_edition(&me.edition)?,
             crate_type: sandbox::CrateType::Binary,
             tests: me.tests,
+            doctests: false,
+            test_filter: None,
             backtrace: false,
+            profile: sandbox::ProfileOptions::default(),
             code: me.code,
+            proc_macro: None,
+            manifest: None,
         })
     }
 }
@@ -1120,6 +2125,71 @@ impl From<sandbox::ExecuteResponse> for EvaluateResponse {
     }
 }
 
+impl limits::SourceCode for CompileRequest {
+    fn source_code(&self) -> Vec<&str> {
+        source_code(&self.code, &[&self.proc_macro, &self.manifest])
+    }
+}
+
+impl limits::SourceCode for ExecuteRequest {
+    fn source_code(&self) -> Vec<&str> {
+        source_code(&self.code, &[&self.proc_macro, &self.manifest])
+    }
+}
+
+impl limits::SourceCode for MacroExpansionRequest {
+    fn source_code(&self) -> Vec<&str> {
+        source_code(&self.code, &[&self.proc_macro])
+    }
+}
+
+impl limits::SourceCode for FormatRequest {
+    fn source_code(&self) -> Vec<&str> {
+        vec![&self.code]
+    }
+}
+
+impl limits::SourceCode for ClippyRequest {
+    fn source_code(&self) -> Vec<&str> {
+        vec![&self.code]
+    }
+}
+
+impl limits::SourceCode for MiriRequest {
+    fn source_code(&self) -> Vec<&str> {
+        vec![&self.code]
+    }
+}
+
+impl limits::SourceCode for DocRequest {
+    fn source_code(&self) -> Vec<&str> {
+        vec![&self.code]
+    }
+}
+
+impl limits::SourceCode for BenchmarkRequest {
+    fn source_code(&self) -> Vec<&str> {
+        vec![&self.code]
+    }
+}
+
+impl limits::SourceCode for MetaGistCreateRequest {
+    fn source_code(&self) -> Vec<&str> {
+        vec![&self.code]
+    }
+}
+
+impl limits::SourceCode for EvaluateRequest {
+    fn source_code(&self) -> Vec<&str> {
+        vec![&self.code]
+    }
+}
+
+fn source_code<'a>(code: &'a str, extra: &[&'a Option<String>]) -> Vec<&'a str> {
+    let extra = extra.iter().flat_map(|s| s.as_deref());
+    std::iter::once(code).chain(extra).collect()
+}
+
 fn parse_target(s: &str) -> Result<sandbox::CompileTarget> {
     Ok(match s {
         "asm" => sandbox::CompileTarget::Assembly(
@@ -1131,6 +2201,7 @@ fn parse_target(s: &str) -> Result<sandbox::CompileTarget> {
         "mir" => sandbox::CompileTarget::Mir,
         "hir" => sandbox::CompileTarget::Hir,
         "wasm" => sandbox::CompileTarget::Wasm,
+        "size-analysis" => sandbox::CompileTarget::SizeAnalysis,
         value => InvalidTargetSnafu { value }.fail()?,
     })
 }
@@ -1159,6 +2230,40 @@ fn parse_process_assembly(s: &str) -> Result<sandbox::ProcessAssembly> {
     })
 }
 
+fn check_proc_macro_consumer(
+    proc_macro: &Option<String>,
+    crate_type: sandbox::CrateType,
+) -> Result<()> {
+    if proc_macro.is_some() && crate_type != sandbox::CrateType::Binary {
+        return ProcMacroRequiresBinarySnafu.fail();
+    }
+    Ok(())
+}
+
+fn check_manifest(manifest: &Option<String>) -> Result<()> {
+    if let Some(manifest) = manifest {
+        manifest::validate(manifest).context(InvalidManifestSnafu)?;
+    }
+    Ok(())
+}
+
+fn parse_mir_phase(s: &str) -> Result<sandbox::MirPhase> {
+    Ok(match s {
+        "built" => sandbox::MirPhase::Built,
+        "analysis" => sandbox::MirPhase::Analysis,
+        "optimized" => sandbox::MirPhase::Optimized,
+        value => InvalidMirPhaseSnafu { value }.fail()?,
+    })
+}
+
+fn parse_wasm_target(s: &str) -> Result<sandbox::WasmTarget> {
+    Ok(match s {
+        "wasm32-unknown-unknown" => sandbox::WasmTarget::Unknown,
+        "wasm32-wasi" => sandbox::WasmTarget::Wasi,
+        value => InvalidWasmTargetSnafu { value }.fail()?,
+    })
+}
+
 fn parse_channel(s: &str) -> Result<sandbox::Channel> {
     Ok(match s {
         "stable" => sandbox::Channel::Stable,
@@ -1176,6 +2281,27 @@ fn parse_mode(s: &str) -> Result<sandbox::Mode> {
     })
 }
 
+fn parse_opt_level(s: &str) -> Result<sandbox::OptLevel> {
+    Ok(match s {
+        "0" => sandbox::OptLevel::Zero,
+        "1" => sandbox::OptLevel::One,
+        "2" => sandbox::OptLevel::Two,
+        "3" => sandbox::OptLevel::Three,
+        "s" => sandbox::OptLevel::Size,
+        "z" => sandbox::OptLevel::MinSize,
+        value => InvalidOptLevelSnafu { value }.fail()?,
+    })
+}
+
+fn parse_debug_info(s: &str) -> Result<sandbox::DebugInfo> {
+    Ok(match s {
+        "none" => sandbox::DebugInfo::None,
+        "limited" => sandbox::DebugInfo::Limited,
+        "full" => sandbox::DebugInfo::Full,
+        value => InvalidDebugInfoSnafu { value }.fail()?,
+    })
+}
+
 fn parse_edition(s: &str) -> Result<Option<sandbox::Edition>> {
     Ok(match s {
         "" => None,
@@ -1186,6 +2312,14 @@ fn parse_edition(s: &str) -> Result<Option<sandbox::Edition>> {
     })
 }
 
+fn parse_doc_format(s: &str) -> Result<sandbox::DocFormat> {
+    Ok(match s {
+        "html" => sandbox::DocFormat::Html,
+        "json" => sandbox::DocFormat::Json,
+        value => InvalidDocFormatSnafu { value }.fail()?,
+    })
+}
+
 fn parse_crate_type(s: &str) -> Result<sandbox::CrateType> {
     use crate::sandbox::{CrateType::*, LibraryType::*};
     Ok(match s {
@@ -1203,3 +2337,87 @@ fn parse_crate_type(s: &str) -> Result<sandbox::CrateType> {
 fn default_crate_type() -> String {
     "bin".into()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io;
+
+    fn io_error() -> io::Error {
+        io::ErrorKind::NotFound.into()
+    }
+
+    #[test]
+    fn error_codes_and_statuses() {
+        let cases = [
+            (
+                Error::InvalidChannel {
+                    value: "lunar".into(),
+                },
+                "InvalidChannel",
+                400,
+            ),
+            (Error::RequestMissing, "RequestMissing", 400),
+            (
+                Error::CrateNotFound {
+                    name: "nope".into(),
+                },
+                "CrateNotFound",
+                404,
+            ),
+            (Error::CachePoisoned, "CachePoisoned", 500),
+            (Error::DocHosting { source: io_error() }, "DocHosting", 500),
+            (
+                Error::RequestRejected {
+                    source: limits::Error::RequestTooLarge { limit: 1 },
+                },
+                "RequestTooLarge",
+                413,
+            ),
+            (
+                Error::RequestRejected {
+                    source: limits::Error::CodeContainsNul,
+                },
+                "CodeContainsNul",
+                400,
+            ),
+            (
+                Error::Execution {
+                    source: sandbox::Error::JobSpecContainsNul,
+                },
+                "JobSpecContainsNul",
+                400,
+            ),
+            (
+                Error::Compilation {
+                    source: sandbox::Error::UnableToStartCompiler { source: io_error() },
+                },
+                "UnableToStartCompiler",
+                503,
+            ),
+            (
+                Error::Compilation {
+                    source: sandbox::Error::OutputMissing,
+                },
+                "OutputMissing",
+                502,
+            ),
+            (
+                Error::Caching {
+                    source: sandbox::Error::UnableToCreateTempDir { source: io_error() },
+                },
+                "UnableToCreateTempDir",
+                500,
+            ),
+        ];
+
+        for (error, code, status) in &cases {
+            assert_eq!(
+                (error.code(), error.status_code()),
+                (*code, *status),
+                "{}",
+                error
+            );
+        }
+    }
+}
@@ -0,0 +1,192 @@
+// Checks the partial Cargo.toml that a request may include. Only
+// settings that affect how the playground's code is built are
+// allowed; anything that would need to download code or change where
+// it comes from is rejected. `modify-cargo-toml` merges the result
+// into the playground's own manifest and checks that dependencies
+// name one of the available crates.
+
+use snafu::prelude::*;
+use toml::{value::Table, Value};
+
+const SECTIONS: &[&str] = &["profile", "features", "lib", "dependencies"];
+const PROFILES: &[&str] = &["dev", "release"];
+// Profile settings are applied to the playground crate alone, so that
+// the preinstalled crates don't need to be rebuilt. Cargo doesn't
+// allow `panic` or `lto` to be set for a single package.
+const PROFILE_KEYS: &[&str] = &[
+    "codegen-units",
+    "debug",
+    "debug-assertions",
+    "incremental",
+    "opt-level",
+    "overflow-checks",
+    "strip",
+];
+const LIB_KEYS: &[&str] = &["doctest", "harness", "test"];
+const DEPENDENCY_KEYS: &[&str] = &["default-features", "features", "optional"];
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("The manifest is not valid TOML: {}", source))]
+    Parsing { source: toml::de::Error },
+    #[snafu(display("The manifest may not contain `{}`", key))]
+    NotAllowed { key: String },
+    #[snafu(display("`{}` must be a table", key))]
+    NotATable { key: String },
+    #[snafu(display(
+        "The dependency `{}` must be \"*\" or a table; the version cannot be changed",
+        name
+    ))]
+    InvalidDependency { name: String },
+    #[snafu(display(
+        "The default features of `{}` cannot be turned off; the crate is already built with them",
+        name
+    ))]
+    DefaultFeaturesRequired { name: String },
+}
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+pub fn validate(manifest: &str) -> Result<()> {
+    let manifest: Table = toml::from_str(manifest).context(ParsingSnafu)?;
+
+    only_keys(&manifest, SECTIONS, "")?;
+
+    if let Some(profiles) = manifest.get("profile") {
+        let profiles = table(profiles, "profile")?;
+        only_keys(profiles, PROFILES, "profile.")?;
+
+        for (name, profile) in profiles {
+            let prefix = format!("profile.{}", name);
+            let profile = table(profile, &prefix)?;
+            only_keys(profile, PROFILE_KEYS, &format!("{}.", prefix))?;
+        }
+    }
+
+    if let Some(features) = manifest.get("features") {
+        table(features, "features")?;
+    }
+
+    if let Some(lib) = manifest.get("lib") {
+        let lib = table(lib, "lib")?;
+        only_keys(lib, LIB_KEYS, "lib.")?;
+    }
+
+    if let Some(dependencies) = manifest.get("dependencies") {
+        let dependencies = table(dependencies, "dependencies")?;
+
+        for (name, dependency) in dependencies {
+            match dependency {
+                Value::String(version) if version == "*" => {}
+                Value::Table(dependency) => {
+                    only_keys(
+                        dependency,
+                        DEPENDENCY_KEYS,
+                        &format!("dependencies.{}.", name),
+                    )?;
+
+                    // Features can only be added to the ones that the
+                    // preinstalled crates were built with
+                    let default_features = dependency.get("default-features");
+                    ensure!(
+                        default_features.and_then(Value::as_bool) != Some(false),
+                        DefaultFeaturesRequiredSnafu { name }
+                    );
+                }
+                _ => InvalidDependencySnafu { name }.fail()?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn table<'a>(value: &'a Value, key: &str) -> Result<&'a Table> {
+    value.as_table().context(NotATableSnafu { key })
+}
+
+fn only_keys(table: &Table, allowed: &[&str], prefix: &str) -> Result<()> {
+    match table.keys().find(|k| !allowed.contains(&k.as_str())) {
+        Some(key) => NotAllowedSnafu {
+            key: format!("{}{}", prefix, key),
+        }
+        .fail(),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn build_settings_allowed() {
+        let manifest = r#"
+            [profile.release]
+            opt-level = "s"
+            overflow-checks = true
+
+            [features]
+            default = ["fast"]
+            fast = []
+
+            [lib]
+            doctest = false
+
+            [dependencies]
+            rand = "*"
+            serde = { features = ["derive"], default-features = true }
+        "#;
+
+        assert!(validate(manifest).is_ok());
+    }
+
+    #[test]
+    fn unknown_settings_rejected() {
+        let error = validate("[package]\nbuild = \"build.rs\"").unwrap_err();
+        assert_eq!(error.to_string(), "The manifest may not contain `package`");
+
+        let error = validate("[profile.release]\nrpath = true").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The manifest may not contain `profile.release.rpath`"
+        );
+
+        let error = validate("[profile.release]\npanic = \"abort\"").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The manifest may not contain `profile.release.panic`"
+        );
+
+        let error = validate("[profile.custom]\nopt-level = 1").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The manifest may not contain `profile.custom`"
+        );
+    }
+
+    #[test]
+    fn dependency_sources_rejected() {
+        let error = validate("[dependencies]\nrand = \"0.7\"").unwrap_err();
+        assert!(matches!(error, Error::InvalidDependency { .. }));
+
+        let error =
+            validate("[dependencies.rand]\ngit = \"https://example.com/rand\"").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The manifest may not contain `dependencies.rand.git`"
+        );
+    }
+
+    #[test]
+    fn default_features_required() {
+        let error = validate("[dependencies.serde]\ndefault-features = false").unwrap_err();
+        assert!(matches!(error, Error::DefaultFeaturesRequired { .. }));
+    }
+
+    #[test]
+    fn invalid_toml_rejected() {
+        let error = validate("[profile").unwrap_err();
+        assert!(matches!(error, Error::Parsing { .. }));
+    }
+}
@@ -1,19 +1,59 @@
-use serde_derive::Deserialize;
+use serde_derive::{Deserialize, Serialize};
 use snafu::{ResultExt, Snafu};
-use std::{ffi::OsStr, fmt, io, os::unix::fs::PermissionsExt, string, time::Duration};
+use std::{
+    collections::BTreeMap, ffi::OsStr, fmt, io, os::unix::fs::PermissionsExt, string,
+    time::Duration,
+};
 use tokio::{
     process::Command,
     runtime::{Builder, Runtime},
 };
 
+pub use crate::{
+    benchmark::Benchmark,
+    dependency_graph::DependencyGraph,
+    size_analysis::SizeAnalysis,
+    test_results::{TestResults, TestStatus},
+};
+
 const DOCKER_PROCESS_TIMEOUT_SOFT: Duration = Duration::from_secs(10);
 const DOCKER_PROCESS_TIMEOUT_HARD: Duration = Duration::from_secs(12);
 
+// Benchmarks are built in release mode and then run once per sample
+const BENCHMARK_TIMEOUT_SOFT: Duration = Duration::from_secs(60);
+const BENCHMARK_TIMEOUT_HARD: Duration = Duration::from_secs(62);
+
+// Where the source of a procedural macro crate is placed alongside the
+// playground crate that uses it
+const PROC_MACRO_SOURCE: &str = "/playground/proc-macro.rs";
+
+// Where the job spec is placed in the container. The version must
+// change whenever the images need to change to understand the spec.
+const JOB_SPEC_FILE: &str = "/playground-job.toml";
+const JOB_SPEC_VERSION: u32 = 1;
+
 #[derive(Debug, Deserialize)]
 struct CrateInformationInner {
     name: String,
     version: String,
     id: String,
+    // Older versions of the crate information only had the fields above
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    documentation: Option<String>,
+    #[serde(default)]
+    repository: Option<String>,
+    #[serde(default)]
+    license: Option<String>,
+    #[serde(default)]
+    features: Vec<String>,
+    #[serde(default)]
+    available_features: Vec<String>,
+    #[serde(default)]
+    sources: Vec<CrateSource>,
+    #[serde(default)]
+    aliases: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -21,12 +61,55 @@ pub struct CrateInformation {
     pub name: String,
     pub version: String,
     pub id: String,
+    pub description: Option<String>,
+    pub documentation: Option<String>,
+    pub repository: Option<String>,
+    pub license: Option<String>,
+    pub features: Vec<String>,
+    pub available_features: Vec<String>,
+    /// Empty when the crate is only present as a dependency
+    pub sources: Vec<CrateSource>,
+    /// Older ids that code can still use to refer to this crate
+    pub aliases: Vec<String>,
+}
+
+/// Why a crate was included in the playground
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CrateSource {
+    TopDownloads,
+    Cookbook,
+    Addition,
 }
 
 impl From<CrateInformationInner> for CrateInformation {
     fn from(me: CrateInformationInner) -> Self {
-        let CrateInformationInner { name, version, id } = me;
-        Self { name, version, id }
+        let CrateInformationInner {
+            name,
+            version,
+            id,
+            description,
+            documentation,
+            repository,
+            license,
+            features,
+            available_features,
+            sources,
+            aliases,
+        } = me;
+        Self {
+            name,
+            version,
+            id,
+            description,
+            documentation,
+            repository,
+            license,
+            features,
+            available_features,
+            sources,
+            aliases,
+        }
     }
 }
 
@@ -49,6 +132,12 @@ pub enum Error {
     UnableToCreateSourceFile { source: io::Error },
     #[snafu(display("Unable to set permissions for source file: {}", source))]
     UnableToSetSourcePermissions { source: io::Error },
+    #[snafu(display("Unable to serialize the job spec: {}", source))]
+    UnableToSerializeJobSpec { source: toml::ser::Error },
+    #[snafu(display("Unable to create the job spec file: {}", source))]
+    UnableToCreateJobSpec { source: io::Error },
+    #[snafu(display("The command and its environment may not contain NUL bytes"))]
+    JobSpecContainsNul,
 
     #[snafu(display("Unable to start the compiler: {}", source))]
     UnableToStartCompiler { source: io::Error },
@@ -70,6 +159,8 @@ pub enum Error {
     UnableToReadOutput { source: io::Error },
     #[snafu(display("Unable to read crate information: {}", source))]
     UnableToParseCrateInformation { source: ::serde_json::Error },
+    #[snafu(display("Unable to read the dependency graph: {}", source))]
+    UnableToParseDependencyGraph { source: ::serde_json::Error },
     #[snafu(display("Output was not valid UTF-8: {}", source))]
     OutputNotUtf8 { source: string::FromUtf8Error },
     #[snafu(display("Output was missing"))]
@@ -134,14 +225,26 @@ impl Sandbox {
         self.runtime.block_on(self.sandbox.miri(req))
     }
 
+    pub fn doc(&self, req: &DocRequest) -> Result<DocResponse> {
+        self.runtime.block_on(self.sandbox.doc(req))
+    }
+
     pub fn macro_expansion(&self, req: &MacroExpansionRequest) -> Result<MacroExpansionResponse> {
         self.runtime.block_on(self.sandbox.macro_expansion(req))
     }
 
+    pub fn benchmark(&self, req: &BenchmarkRequest) -> Result<BenchmarkResponse> {
+        self.runtime.block_on(self.sandbox.benchmark(req))
+    }
+
     pub fn crates(&self) -> Result<Vec<CrateInformation>> {
         self.runtime.block_on(self.sandbox.crates())
     }
 
+    pub fn dependency_graph(&self) -> Result<DependencyGraph> {
+        self.runtime.block_on(self.sandbox.dependency_graph())
+    }
+
     pub fn version(&self, channel: Channel) -> Result<Version> {
         self.runtime.block_on(self.sandbox.version(channel))
     }
@@ -185,11 +288,6 @@ fn basic_secure_docker_command() -> Command {
         "512m",
         "--memory-swap",
         "640m",
-        "--env",
-        format!(
-            "PLAYGROUND_TIMEOUT={}",
-            DOCKER_PROCESS_TIMEOUT_SOFT.as_secs()
-        ),
     );
 
     if cfg!(feature = "fork-bomb-prevention") {
@@ -216,6 +314,7 @@ fn build_execution_command(
 
     match (target, req.crate_type(), tests) {
         (Some(Wasm), _, _) => cmd.push("wasm"),
+        (Some(SizeAnalysis), _, _) => cmd.push("size-analysis"),
         (Some(_), _, _) => cmd.push("rustc"),
         (_, _, true) => cmd.push("test"),
         (_, Library(_), _) => cmd.push("build"),
@@ -252,11 +351,19 @@ fn build_execution_command(
                     Att => cmd.push("llvm-args=-x86-asm-syntax=att"),
                     Intel => cmd.push("llvm-args=-x86-asm-syntax=intel"),
                 }
+
+                // Line information is needed to map the assembly back
+                // to the source; debug builds already have it
+                if mode == Release {
+                    cmd.push("-C");
+                    cmd.push("debuginfo=1");
+                }
             }
             LlvmIr => cmd.push("--emit=llvm-ir"),
             Mir => cmd.push("--emit=mir"),
             Hir => cmd.push("-Zunpretty=hir"),
             Wasm => { /* handled by cargo-wasm wrapper */ }
+            SizeAnalysis => { /* handled by cargo-size-analysis wrapper */ }
         }
     }
 
@@ -264,20 +371,22 @@ fn build_execution_command(
 }
 
 fn set_execution_environment(
-    cmd: &mut Command,
+    job: &mut JobSpec,
     target: Option<CompileTarget>,
-    req: impl CrateTypeRequest + EditionRequest + BacktraceRequest,
+    req: impl CrateTypeRequest + EditionRequest + BacktraceRequest + ProfileRequest + ManifestRequest,
 ) {
     use self::CompileTarget::*;
 
     if let Some(Wasm) = target {
-        cmd.args(&["--env", "PLAYGROUND_NO_DEPENDENCIES=true"]);
-        cmd.args(&["--env", "PLAYGROUND_RELEASE_LTO=true"]);
+        job.no_dependencies = true;
+        job.release_lto = true;
     }
 
-    cmd.apply_crate_type(&req);
-    cmd.apply_edition(&req);
-    cmd.apply_backtrace(&req);
+    job.apply_crate_type(&req);
+    job.apply_edition(&req);
+    job.apply_backtrace(&req);
+    job.apply_profile(&req);
+    job.apply_manifest(&req);
 }
 
 pub mod fut {
@@ -285,34 +394,43 @@ pub mod fut {
     use std::{
         collections::BTreeMap,
         ffi::OsStr,
+        fmt,
         io::ErrorKind,
         path::{Path, PathBuf},
+        time::Duration,
     };
     use tempdir::TempDir;
     use tokio::{fs, process::Command, time};
 
     use super::{
-        basic_secure_docker_command, build_execution_command, set_execution_environment,
-        vec_to_str, wide_open_permissions, BacktraceRequest, Channel, ClippyRequest,
-        ClippyResponse, CompileRequest, CompileResponse, CompileTarget,
-        CompilerExecutionTimedOutSnafu, CrateInformation, CrateInformationInner, CrateType,
-        CrateTypeRequest, DemangleAssembly, DockerCommandExt, EditionRequest, ExecuteRequest,
-        ExecuteResponse, FormatRequest, FormatResponse, MacroExpansionRequest,
-        MacroExpansionResponse, MiriRequest, MiriResponse, MissingCompilerIdSnafu, Mode,
-        OutputMissingSnafu, ProcessAssembly, Result, UnableToCreateOutputDirSnafu,
-        UnableToCreateSourceFileSnafu, UnableToCreateTempDirSnafu,
+        basic_secure_docker_command, build_execution_command, build_mir_dump_args,
+        group_source_lines, set_execution_environment, vec_to_str, wide_open_permissions,
+        BenchmarkRequest, BenchmarkResponse, Channel, ClippyRequest, ClippyResponse,
+        CompileRequest, CompileResponse, CompileTarget, CompilerExecutionTimedOutSnafu,
+        CrateInformation, CrateInformationInner, CrateType, CrateTypeRequest, DemangleAssembly,
+        DependencyGraph, DocFormat, DocRequest, DocResponse, Edition, EditionRequest,
+        ExecuteRequest, ExecuteResponse, FormatRequest, FormatResponse, FunctionLocation, JobSpec,
+        JobSpecContainsNulSnafu, LineRange, MacroExpansionRequest, MacroExpansionResponse, MirDump,
+        MirPhase, MiriRequest, MiriResponse, MissingCompilerIdSnafu, OutputMissingSnafu,
+        ProcMacroRequest, ProcessAssembly, Result, UnableToCreateJobSpecSnafu,
+        UnableToCreateOutputDirSnafu, UnableToCreateSourceFileSnafu, UnableToCreateTempDirSnafu,
         UnableToGetOutputFromCompilerSnafu, UnableToParseCrateInformationSnafu,
-        UnableToReadOutputSnafu, UnableToRemoveCompilerSnafu, UnableToSetOutputPermissionsSnafu,
+        UnableToParseDependencyGraphSnafu, UnableToReadOutputSnafu, UnableToRemoveCompilerSnafu,
+        UnableToSerializeJobSpecSnafu, UnableToSetOutputPermissionsSnafu,
         UnableToSetSourcePermissionsSnafu, UnableToStartCompilerSnafu,
         UnableToWaitForCompilerSnafu, Version, VersionDateMissingSnafu, VersionHashMissingSnafu,
-        VersionReleaseMissingSnafu, DOCKER_PROCESS_TIMEOUT_HARD,
+        VersionReleaseMissingSnafu, BENCHMARK_TIMEOUT_HARD, BENCHMARK_TIMEOUT_SOFT,
+        DOCKER_PROCESS_TIMEOUT_HARD, JOB_SPEC_FILE, MAX_MIR_DUMPS, MAX_MIR_DUMP_SIZE, MIR_DUMP_DIR,
+        PROC_MACRO_SOURCE,
     };
 
     pub struct Sandbox {
         #[allow(dead_code)]
         scratch: TempDir,
         input_file: PathBuf,
+        proc_macro_file: PathBuf,
         output_dir: PathBuf,
+        job_file: PathBuf,
     }
 
     impl Sandbox {
@@ -323,7 +441,9 @@ pub mod fut {
             // enough.
             let scratch = TempDir::new("playground").context(UnableToCreateTempDirSnafu)?;
             let input_file = scratch.path().join("input.rs");
+            let proc_macro_file = scratch.path().join("proc_macro.rs");
             let output_dir = scratch.path().join("output");
+            let job_file = scratch.path().join("job.toml");
 
             fs::create_dir(&output_dir)
                 .await
@@ -335,14 +455,17 @@ pub mod fut {
             Ok(Sandbox {
                 scratch,
                 input_file,
+                proc_macro_file,
                 output_dir,
+                job_file,
             })
         }
 
         pub async fn compile(&self, req: &CompileRequest) -> Result<CompileResponse> {
             self.write_source_code(&req.code).await?;
+            self.write_proc_macro_code(req).await?;
 
-            let command = self.compile_command(req.target, req.channel, req.mode, req.tests, req);
+            let command = self.compile_command(req).await?;
 
             let output = run_command_with_timeout(command).await?;
 
@@ -390,21 +513,167 @@ pub mod fut {
                 }
             };
 
-            if let CompileTarget::Assembly(_, demangle, process) = req.target {
-                if demangle == DemangleAssembly::Demangle {
-                    code = crate::asm_cleanup::demangle_asm(&code);
+            let mut source_lines = Vec::new();
+            let mut mir_dumps = Vec::new();
+            let mut mir_graphviz = None;
+            let mut wasm_binary = None;
+            let mut wasm_js = None;
+            let mut size_analysis = None;
+            let mut origins: Vec<_> = (0..code.lines().count()).map(Some).collect();
+
+            let functions = match req.target {
+                CompileTarget::Assembly(_, demangle, process) => {
+                    source_lines =
+                        crate::asm_cleanup::source_lines(&code, req.crate_type.file_name());
+                    let functions = crate::function_filter::asm_functions(&code);
+
+                    if demangle == DemangleAssembly::Demangle {
+                        code = crate::asm_cleanup::demangle_asm(&code);
+                    }
+
+                    if process == ProcessAssembly::Filter {
+                        let (filtered, filtered_origins) =
+                            crate::asm_cleanup::filter_asm_tracking_origin(&code);
+                        code = filtered;
+                        origins = filtered_origins;
+                    }
+
+                    Some(functions)
+                }
+                CompileTarget::LlvmIr => Some(crate::function_filter::llvm_ir_functions(&code)),
+                CompileTarget::Mir => {
+                    let (dumps, truncated) =
+                        read_mir_dumps(&self.output_dir.join(MIR_DUMP_DIR), req.mir.phase).await?;
+                    if truncated {
+                        use std::fmt::Write;
+                        write!(
+                            &mut stderr,
+                            "\nThe MIR dump filter matched too many passes; only some are shown",
+                        )
+                        .expect("Unable to write to a string");
+                    }
+                    let phase_dumps: Vec<_> = dumps
+                        .iter()
+                        .filter(|d| d.is_end_of(req.mir.phase))
+                        .collect();
+
+                    if req.mir.phase != MirPhase::Optimized && output.status.success() {
+                        if phase_dumps.is_empty() {
+                            use std::fmt::Write;
+                            write!(
+                                &mut stderr,
+                                "\nUnable to locate the {:?} MIR, showing the optimized MIR instead",
+                                req.mir.phase,
+                            )
+                            .expect("Unable to write to a string");
+                        } else {
+                            code = phase_dumps
+                                .iter()
+                                .map(|d| d.mir.as_str())
+                                .collect::<Vec<_>>()
+                                .join("\n");
+                        }
+                    }
+
+                    if req.mir.graphviz {
+                        let graphs: Vec<_> = phase_dumps
+                            .iter()
+                            .flat_map(|d| d.graphviz.as_deref())
+                            .collect();
+                        if !graphs.is_empty() {
+                            mir_graphviz = Some(graphs.join("\n"));
+                        }
+                    }
+
+                    // Dumps that were only made to find the phase
+                    // aren't interesting on their own
+                    if req.mir.dump.is_some() {
+                        mir_dumps = dumps;
+                    }
+
+                    Some(crate::function_filter::mir_functions(&code))
+                }
+                CompileTarget::Hir => {
+                    if output.status.success() {
+                        code = self
+                            .format_output(code, req.edition, req.target, &mut stderr)
+                            .await;
+                    }
+                    None
                 }
+                CompileTarget::Wasm => {
+                    // With wasm-bindgen, the original module no longer
+                    // matches the generated JavaScript
+                    let binary_name = if req.wasm.bindgen {
+                        "compilation_bg.wasm"
+                    } else {
+                        "compilation.wasm"
+                    };
+
+                    if req.wasm.binary {
+                        let binary = read_bytes(&self.output_dir.join(binary_name)).await?;
+                        wasm_binary = binary.map(base64::encode);
+                    }
+                    if req.wasm.bindgen {
+                        wasm_js = read(&self.output_dir.join("compilation.js")).await?;
+                    }
 
-                if process == ProcessAssembly::Filter {
-                    code = crate::asm_cleanup::filter_asm(&code);
+                    None
+                }
+                CompileTarget::SizeAnalysis => {
+                    let sections = read(&self.output_dir.join("compilation.sections"))
+                        .await?
+                        .unwrap_or_default();
+                    let analysis = crate::size_analysis::analyze(
+                        &sections,
+                        &code,
+                        req.size_analysis.symbol_limit,
+                    );
+                    code = crate::size_analysis::report(&analysis);
+                    size_analysis = Some(analysis);
+
+                    None
                 }
-            } else if CompileTarget::Hir == req.target {
-                // TODO: Run rustfmt on the generated HIR.
+            };
+
+            let mut function_locations = Vec::new();
+
+            if let Some(functions) = functions {
+                let selection =
+                    crate::function_filter::select(&code, &origins, &functions, &req.functions);
+                code = selection.code;
+                origins = selection
+                    .origins
+                    .into_iter()
+                    .map(|origin| origin.and_then(|i| origins[i]))
+                    .collect();
+                function_locations = selection
+                    .functions
+                    .into_iter()
+                    .map(|(name, lines)| FunctionLocation {
+                        name,
+                        lines: lines.map(|(start, end)| LineRange { start, end }),
+                    })
+                    .collect();
             }
 
+            let source_map = group_source_lines(
+                origins
+                    .into_iter()
+                    .map(|origin| origin.and_then(|i| source_lines.get(i).copied().flatten()))
+                    .collect(),
+            );
+
             Ok(CompileResponse {
                 success: output.status.success(),
                 code,
+                source_map,
+                functions: function_locations,
+                mir_dumps,
+                mir_graphviz,
+                wasm_binary,
+                wasm_js,
+                size_analysis,
                 stdout,
                 stderr,
             })
@@ -412,20 +681,29 @@ pub mod fut {
 
         pub async fn execute(&self, req: &ExecuteRequest) -> Result<ExecuteResponse> {
             self.write_source_code(&req.code).await?;
-            let command = self.execute_command(req.channel, req.mode, req.tests, req);
+            self.write_proc_macro_code(req).await?;
+            let command = self.execute_command(req).await?;
 
             let output = run_command_with_timeout(command).await?;
+            let stdout = vec_to_str(output.stdout)?;
+
+            let test_results = if req.tests {
+                crate::test_results::parse(&stdout)
+            } else {
+                None
+            };
 
             Ok(ExecuteResponse {
                 success: output.status.success(),
-                stdout: vec_to_str(output.stdout)?,
+                stdout,
                 stderr: vec_to_str(output.stderr)?,
+                test_results,
             })
         }
 
         pub async fn format(&self, req: &FormatRequest) -> Result<FormatResponse> {
             self.write_source_code(&req.code).await?;
-            let command = self.format_command(req);
+            let command = self.format_command(req).await?;
 
             let output = run_command_with_timeout(command).await?;
 
@@ -441,7 +719,7 @@ pub mod fut {
 
         pub async fn clippy(&self, req: &ClippyRequest) -> Result<ClippyResponse> {
             self.write_source_code(&req.code).await?;
-            let command = self.clippy_command(req);
+            let command = self.clippy_command(req).await?;
 
             let output = run_command_with_timeout(command).await?;
 
@@ -452,9 +730,27 @@ pub mod fut {
             })
         }
 
+        pub async fn benchmark(&self, req: &BenchmarkRequest) -> Result<BenchmarkResponse> {
+            self.write_source_code(&req.code).await?;
+            let command = self.benchmark_command(req).await?;
+
+            let output = run_command_with_timeout_of(command, BENCHMARK_TIMEOUT_HARD).await?;
+
+            let results = read(&self.output_dir.join("benchmarks.json"))
+                .await?
+                .unwrap_or_default();
+
+            Ok(BenchmarkResponse {
+                success: output.status.success(),
+                benchmarks: crate::benchmark::summarize(&results),
+                stdout: vec_to_str(output.stdout)?,
+                stderr: vec_to_str(output.stderr)?,
+            })
+        }
+
         pub async fn miri(&self, req: &MiriRequest) -> Result<MiriResponse> {
             self.write_source_code(&req.code).await?;
-            let command = self.miri_command(req);
+            let command = self.miri_command(req).await?;
 
             let output = run_command_with_timeout(command).await?;
 
@@ -465,19 +761,63 @@ pub mod fut {
             })
         }
 
+        pub async fn doc(&self, req: &DocRequest) -> Result<DocResponse> {
+            self.write_source_code(&req.code).await?;
+            let command = self.doc_command(req).await?;
+
+            let output = run_command_with_timeout(command).await?;
+
+            let (archive, json) = match req.format {
+                DocFormat::Html => (read_bytes(&self.output_dir.join("doc.tar.gz")).await?, None),
+                DocFormat::Json => (None, read(&self.output_dir.join("doc.json")).await?),
+            };
+
+            Ok(DocResponse {
+                success: output.status.success(),
+                archive,
+                json,
+                stdout: vec_to_str(output.stdout)?,
+                stderr: vec_to_str(output.stderr)?,
+            })
+        }
+
         pub async fn macro_expansion(
             &self,
             req: &MacroExpansionRequest,
         ) -> Result<MacroExpansionResponse> {
             self.write_source_code(&req.code).await?;
-            let command = self.macro_expansion_command(req);
+            self.write_proc_macro_code(req).await?;
+            let command = self.macro_expansion_command(req).await?;
 
             let output = run_command_with_timeout(command).await?;
+            let success = output.status.success();
+            let mut stdout = vec_to_str(output.stdout)?;
+            let mut stderr = vec_to_str(output.stderr)?;
+
+            if success {
+                if let Some(item) = &req.item {
+                    stdout = match crate::macro_expansion::select_item(&stdout, item) {
+                        Some(selected) => selected,
+                        None => {
+                            use std::fmt::Write;
+                            write!(stderr, "\nNo item named `{}` was found", item)
+                                .expect("Unable to write to a string");
+                            String::new()
+                        }
+                    };
+                }
+
+                if req.format && !stdout.is_empty() {
+                    stdout = self
+                        .format_output(stdout, req.edition, "expanded", &mut stderr)
+                        .await;
+                }
+            }
 
             Ok(MacroExpansionResponse {
-                success: output.status.success(),
-                stdout: vec_to_str(output.stdout)?,
-                stderr: vec_to_str(output.stderr)?,
+                success,
+                stdout,
+                stderr,
             })
         }
 
@@ -497,6 +837,25 @@ pub mod fut {
             Ok(crates)
         }
 
+        pub async fn dependency_graph(&self) -> Result<DependencyGraph> {
+            let mut command = basic_secure_docker_command();
+            command.arg(Channel::Stable.container_name());
+            command.args([
+                "cargo",
+                "metadata",
+                "--format-version",
+                "1",
+                "--offline",
+                "--filter-platform",
+                "x86_64-unknown-linux-gnu",
+            ]);
+
+            let output = run_command_with_timeout(command).await?;
+
+            DependencyGraph::from_metadata(&output.stdout)
+                .context(UnableToParseDependencyGraphSnafu)
+        }
+
         pub async fn version(&self, channel: Channel) -> Result<Version> {
             let mut command = basic_secure_docker_command();
             command.args(&[channel.container_name()]);
@@ -569,6 +928,37 @@ pub mod fut {
             })
         }
 
+        // Runs rustfmt on generated code. Not everything the compiler
+        // prints is valid Rust, so keep the original text when rustfmt
+        // fails and tell the user why it looks the way it does.
+        async fn format_output(
+            &self,
+            code: String,
+            edition: Option<Edition>,
+            kind: impl fmt::Display,
+            stderr: &mut String,
+        ) -> String {
+            let req = FormatRequest {
+                code: code.clone(),
+                edition,
+            };
+
+            match self.format(&req).await {
+                Ok(resp) if resp.success => return resp.code,
+                Ok(_) => {}
+                Err(e) => log::warn!("Unable to format {} output: {}", kind, e),
+            }
+
+            use std::fmt::Write;
+            write!(
+                stderr,
+                "\nUnable to format {} output; showing it unformatted",
+                kind
+            )
+            .expect("Unable to write to a string");
+            code
+        }
+
         async fn write_source_code(&self, code: &str) -> Result<()> {
             fs::write(&self.input_file, code)
                 .await
@@ -585,102 +975,210 @@ pub mod fut {
             Ok(())
         }
 
-        fn compile_command(
-            &self,
-            target: CompileTarget,
-            channel: Channel,
-            mode: Mode,
-            tests: bool,
-            req: impl CrateTypeRequest + EditionRequest + BacktraceRequest,
-        ) -> Command {
-            let mut cmd = self.docker_command(Some(req.crate_type()));
-            set_execution_environment(&mut cmd, Some(target), &req);
+        async fn write_proc_macro_code(&self, req: impl ProcMacroRequest) -> Result<()> {
+            let code = match req.proc_macro() {
+                Some(code) => code,
+                None => return Ok(()),
+            };
+
+            fs::write(&self.proc_macro_file, code)
+                .await
+                .context(UnableToCreateSourceFileSnafu)?;
+            fs::set_permissions(&self.proc_macro_file, wide_open_permissions())
+                .await
+                .context(UnableToSetSourcePermissionsSnafu)?;
+
+            log::debug!(
+                "Wrote {} bytes of procedural macro source to {}",
+                code.len(),
+                self.proc_macro_file.display()
+            );
+            Ok(())
+        }
 
-            let execution_cmd = build_execution_command(Some(target), channel, mode, &req, tests);
+        async fn compile_command(&self, req: &CompileRequest) -> Result<Command> {
+            let CompileRequest {
+                target,
+                channel,
+                mode,
+                tests,
+                ..
+            } = *req;
+
+            let mut job = JobSpec::default();
+            set_execution_environment(&mut job, Some(target), req);
+            job.apply_proc_macro(req);
+
+            if target == CompileTarget::Wasm {
+                job.apply_wasm(&req.wasm);
+            }
+
+            let execution_cmd = build_execution_command(Some(target), channel, mode, req, tests);
 
-            cmd.arg(&channel.container_name()).args(&execution_cmd);
+            job.args(&execution_cmd);
+
+            if target == CompileTarget::Mir {
+                job.args(build_mir_dump_args(&req.mir));
+            }
 
-            log::debug!("Compilation command is {:?}", cmd);
+            log::debug!("Compilation job is {:?}", job);
 
-            cmd
+            self.docker_command(Some(req.crate_type()), channel.container_name(), &job)
+                .await
         }
 
-        fn execute_command(
-            &self,
-            channel: Channel,
-            mode: Mode,
-            tests: bool,
-            req: impl CrateTypeRequest + EditionRequest + BacktraceRequest,
-        ) -> Command {
-            let mut cmd = self.docker_command(Some(req.crate_type()));
-            set_execution_environment(&mut cmd, None, &req);
+        async fn execute_command(&self, req: &ExecuteRequest) -> Result<Command> {
+            let ExecuteRequest {
+                channel,
+                mode,
+                tests,
+                doctests,
+                ref test_filter,
+                ..
+            } = *req;
+
+            let mut job = JobSpec::default();
+            set_execution_environment(&mut job, None, req);
+            job.apply_proc_macro(req);
+
+            let execution_cmd = build_execution_command(None, channel, mode, req, tests);
+
+            job.args(&execution_cmd);
+
+            if doctests {
+                job.arg("--doc");
+            }
+
+            if tests {
+                // Include the output of passing tests so that it can
+                // be returned alongside each result
+                job.args(["--", "--show-output"]);
 
-            let execution_cmd = build_execution_command(None, channel, mode, &req, tests);
+                if channel == Channel::Nightly {
+                    job.args(["-Z", "unstable-options", "--report-time"]);
+                }
 
-            cmd.arg(&channel.container_name()).args(&execution_cmd);
+                if let Some(test_filter) = test_filter {
+                    job.args([test_filter.as_str(), "--exact"]);
+                }
+            }
 
-            log::debug!("Execution command is {:?}", cmd);
+            log::debug!("Execution job is {:?}", job);
 
-            cmd
+            self.docker_command(Some(req.crate_type()), channel.container_name(), &job)
+                .await
         }
 
-        fn format_command(&self, req: impl EditionRequest) -> Command {
+        async fn format_command(&self, req: impl EditionRequest) -> Result<Command> {
             let crate_type = CrateType::Binary;
 
-            let mut cmd = self.docker_command(Some(crate_type));
+            let mut job = JobSpec::default();
+            job.apply_edition(req);
+            job.args(["cargo", "fmt"]);
 
-            cmd.apply_edition(req);
+            log::debug!("Formatting job is {:?}", job);
+
+            self.docker_command(Some(crate_type), "rustfmt", &job).await
+        }
+
+        async fn clippy_command(
+            &self,
+            req: impl CrateTypeRequest + EditionRequest,
+        ) -> Result<Command> {
+            let mut job = JobSpec::default();
+            job.apply_crate_type(&req);
+            job.apply_edition(&req);
+            job.args(["cargo", "clippy"]);
 
-            cmd.arg("rustfmt").args(&["cargo", "fmt"]);
+            log::debug!("Clippy job is {:?}", job);
 
-            log::debug!("Formatting command is {:?}", cmd);
+            self.docker_command(Some(req.crate_type()), "clippy", &job)
+                .await
+        }
 
-            cmd
+        async fn benchmark_command(&self, req: &BenchmarkRequest) -> Result<Command> {
+            let mut job = JobSpec {
+                timeout: BENCHMARK_TIMEOUT_SOFT.as_secs(),
+                ..JobSpec::default()
+            };
+            job.apply_crate_type(req);
+            job.apply_edition(req);
+            job.env("PLAYGROUND_BENCHMARK_SAMPLES", req.samples.to_string());
+            job.args(["cargo", "bench-playground"]);
+
+            log::debug!("Benchmark job is {:?}", job);
+
+            // `#[bench]` is only available on nightly
+            self.docker_command(
+                Some(req.crate_type()),
+                Channel::Nightly.container_name(),
+                &job,
+            )
+            .await
         }
 
-        fn clippy_command(&self, req: impl CrateTypeRequest + EditionRequest) -> Command {
-            let mut cmd = self.docker_command(Some(req.crate_type()));
+        async fn miri_command(&self, req: impl EditionRequest) -> Result<Command> {
+            let mut job = JobSpec::default();
+            job.apply_edition(req);
+            job.args(["cargo", "miri-playground"]);
 
-            cmd.apply_crate_type(&req);
-            cmd.apply_edition(&req);
+            log::debug!("Miri job is {:?}", job);
 
-            cmd.arg("clippy").args(&["cargo", "clippy"]);
+            self.docker_command(None, "miri", &job).await
+        }
+
+        async fn doc_command(&self, req: &DocRequest) -> Result<Command> {
+            let mut job = JobSpec::default();
+            job.apply_crate_type(req);
+            job.apply_edition(req);
+            job.env("PLAYGROUND_DOC_FORMAT", req.format.cargo_ident());
+            job.args(["cargo", "doc-playground"]);
 
-            log::debug!("Clippy command is {:?}", cmd);
+            log::debug!("Documentation job is {:?}", job);
 
-            cmd
+            self.docker_command(Some(req.crate_type()), req.channel.container_name(), &job)
+                .await
         }
 
-        fn miri_command(&self, req: impl EditionRequest) -> Command {
-            let mut cmd = self.docker_command(None);
-            cmd.apply_edition(req);
+        async fn macro_expansion_command(&self, req: &MacroExpansionRequest) -> Result<Command> {
+            let mut job = JobSpec::default();
+            job.apply_edition(req);
+            job.apply_proc_macro(req);
+
+            // `-Zunpretty` is unstable; allow it on the other channels
+            // the same way that the compiler's own build does
+            if req.channel != Channel::Nightly {
+                job.env("RUSTC_BOOTSTRAP", "1");
+            }
 
-            cmd.arg("miri").args(&["cargo", "miri-playground"]);
+            job.args(["cargo", "rustc", "--", "-Zunpretty=expanded"]);
 
-            log::debug!("Miri command is {:?}", cmd);
+            log::debug!("Macro expansion job is {:?}", job);
 
-            cmd
+            self.docker_command(None, req.channel.container_name(), &job)
+                .await
         }
 
-        fn macro_expansion_command(&self, req: impl EditionRequest) -> Command {
-            let mut cmd = self.docker_command(None);
-            cmd.apply_edition(req);
-
-            cmd.arg(&Channel::Nightly.container_name()).args(&[
-                "cargo",
-                "rustc",
-                "--",
-                "-Zunpretty=expanded",
-            ]);
+        async fn write_job_spec(&self, job: &JobSpec) -> Result<()> {
+            ensure!(!job.contains_nul(), JobSpecContainsNulSnafu);
 
-            log::debug!("Macro expansion command is {:?}", cmd);
+            let spec = toml::to_string(job).context(UnableToSerializeJobSpecSnafu)?;
 
-            cmd
+            fs::write(&self.job_file, spec)
+                .await
+                .context(UnableToCreateJobSpecSnafu)
         }
 
-        fn docker_command(&self, crate_type: Option<CrateType>) -> Command {
+        async fn docker_command(
+            &self,
+            crate_type: Option<CrateType>,
+            container_name: &str,
+            job: &JobSpec,
+        ) -> Result<Command> {
             let crate_type = crate_type.unwrap_or(CrateType::Binary);
 
+            self.write_job_spec(job).await?;
+
             let mut mount_input_file = self.input_file.as_os_str().to_os_string();
             mount_input_file.push(":");
             mount_input_file.push("/playground/");
@@ -690,21 +1188,44 @@ pub mod fut {
             mount_output_dir.push(":");
             mount_output_dir.push("/playground-result");
 
+            let mut mount_job_file = self.job_file.as_os_str().to_os_string();
+            mount_job_file.push(":");
+            mount_job_file.push(JOB_SPEC_FILE);
+
             let mut cmd = basic_secure_docker_command();
 
             cmd.arg("--volume")
                 .arg(&mount_input_file)
                 .arg("--volume")
-                .arg(&mount_output_dir);
+                .arg(&mount_output_dir)
+                .arg("--volume")
+                .arg(&mount_job_file);
+
+            // The procedural macro crate is made available to the
+            // playground crate as the `playground_macros` dependency
+            if job.proc_macro.is_some() {
+                let mut mount_proc_macro_file = self.proc_macro_file.as_os_str().to_os_string();
+                mount_proc_macro_file.push(":");
+                mount_proc_macro_file.push(PROC_MACRO_SOURCE);
+
+                cmd.arg("--volume").arg(&mount_proc_macro_file);
+            }
+
+            cmd.arg(container_name);
 
-            cmd
+            Ok(cmd)
         }
     }
 
-    async fn run_command_with_timeout(mut command: Command) -> Result<std::process::Output> {
-        use std::os::unix::process::ExitStatusExt;
+    async fn run_command_with_timeout(command: Command) -> Result<std::process::Output> {
+        run_command_with_timeout_of(command, DOCKER_PROCESS_TIMEOUT_HARD).await
+    }
 
-        let timeout = DOCKER_PROCESS_TIMEOUT_HARD;
+    async fn run_command_with_timeout_of(
+        mut command: Command,
+        timeout: Duration,
+    ) -> Result<std::process::Output> {
+        use std::os::unix::process::ExitStatusExt;
 
         let output = command.output().await.context(UnableToStartCompilerSnafu)?;
 
@@ -768,6 +1289,86 @@ pub mod fut {
         Ok(output)
     }
 
+    // Files are named like `playground.main.1-1-006.SimplifyCfg-initial.after.mir`,
+    // with a matching `.dot` file when graphviz output was requested.
+    //
+    // A broad filter can dump every pass of every item, so only so many
+    // files and bytes are read. The dumps that end `phase` are read
+    // first as they are needed to show that phase. Returns whether any
+    // dumps were left out.
+    pub(super) async fn read_mir_dumps(
+        dir: &Path,
+        phase: MirPhase,
+    ) -> Result<(Vec<MirDump>, bool)> {
+        let mut files = match fs::read_dir(dir).await {
+            Ok(files) => files,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok((Vec::new(), false)),
+            Err(e) => return Err(e).context(UnableToReadOutputSnafu),
+        };
+
+        let mut paths = Vec::new();
+        while let Some(entry) = files.next_entry().await.context(UnableToReadOutputSnafu)? {
+            let path = entry.path();
+            if path.extension() == Some(OsStr::new("mir")) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut dumps = Vec::new();
+        for path in paths {
+            let name = match path.file_stem().and_then(OsStr::to_str) {
+                Some(name) => name,
+                None => continue,
+            };
+            let mut parts = name.splitn(3, '.');
+            let (item, pass) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(_crate_name), Some(item), Some(pass)) => (item.to_string(), pass.to_string()),
+                _ => continue,
+            };
+
+            let dump = MirDump {
+                item,
+                pass,
+                mir: String::new(),
+                graphviz: None,
+            };
+            dumps.push((dump, path));
+        }
+        dumps.sort_by_key(|(dump, _)| !dump.is_end_of(phase));
+
+        let total = dumps.len();
+        dumps.truncate(MAX_MIR_DUMPS);
+
+        let mut size = 0;
+        let mut read_dumps = Vec::new();
+        for (mut dump, path) in dumps {
+            let graphviz_path = path.with_extension("dot");
+            size += file_size(&path).await? + file_size(&graphviz_path).await?;
+            if size > MAX_MIR_DUMP_SIZE {
+                break;
+            }
+
+            dump.mir = read(&path).await?.unwrap_or_default();
+            dump.graphviz = read(&graphviz_path).await?;
+            read_dumps.push((dump, path));
+        }
+
+        let truncated = read_dumps.len() < total;
+        read_dumps.sort_by(|(_, a), (_, b)| a.cmp(b));
+        let dumps = read_dumps.into_iter().map(|(dump, _)| dump).collect();
+
+        Ok((dumps, truncated))
+    }
+
+    async fn file_size(path: &Path) -> Result<u64> {
+        match fs::metadata(path).await {
+            Ok(m) => Ok(m.len()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e).context(UnableToReadOutputSnafu),
+        }
+    }
+
     async fn read(path: &Path) -> Result<Option<String>> {
         match fs::read_to_string(path).await {
             Ok(s) => Ok(Some(s)),
@@ -775,6 +1376,14 @@ pub mod fut {
             Err(e) => Err(e).context(UnableToReadOutputSnafu),
         }
     }
+
+    async fn read_bytes(path: &Path) -> Result<Option<Vec<u8>>> {
+        match fs::read(path).await {
+            Ok(b) => Ok(Some(b)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).context(UnableToReadOutputSnafu),
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -795,104 +1404,324 @@ pub enum ProcessAssembly {
     Raw,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, strum::IntoStaticStr)]
-pub enum CompileTarget {
-    Assembly(AssemblyFlavor, DemangleAssembly, ProcessAssembly),
-    LlvmIr,
-    Mir,
-    Hir,
-    Wasm,
-}
-
-impl CompileTarget {
-    fn extension(&self) -> &'static OsStr {
-        let ext = match *self {
-            CompileTarget::Assembly(_, _, _) => "s",
-            CompileTarget::LlvmIr => "ll",
-            CompileTarget::Mir => "mir",
-            CompileTarget::Hir => "hir",
-            CompileTarget::Wasm => "wat",
-        };
-        OsStr::new(ext)
-    }
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MirPhase {
+    Built,
+    Analysis,
+    Optimized,
 }
 
-impl fmt::Display for CompileTarget {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        use self::CompileTarget::*;
+impl MirPhase {
+    // The compiler dumps the MIR each time it moves on to a new phase,
+    // naming the dump after that phase rather than after a pass, such
+    // as `playground.main.2-1-000.analysis.after.mir`. Checked against
+    // nightly-2026-05-19.
+    fn dump_name(&self) -> &'static str {
+        use self::MirPhase::*;
 
         match *self {
-            Assembly(_, _, _) => "assembly".fmt(f),
-            LlvmIr => "LLVM IR".fmt(f),
-            Mir => "Rust MIR".fmt(f),
-            Hir => "Rust HIR".fmt(f),
-            Wasm => "WebAssembly".fmt(f),
+            Built => "built",
+            Analysis => "analysis",
+            Optimized => "runtime-optimized",
         }
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, strum::IntoStaticStr)]
-pub enum Channel {
-    Stable,
-    Beta,
-    Nightly,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MirOptions {
+    pub phase: MirPhase,
+    /// A `-Zdump-mir` filter selecting additional passes to return
+    pub dump: Option<String>,
+    pub graphviz: bool,
 }
 
-impl Channel {
-    fn container_name(&self) -> &'static str {
-        use self::Channel::*;
-
-        match *self {
-            Stable => "rust-stable",
-            Beta => "rust-beta",
-            Nightly => "rust-nightly",
+impl Default for MirOptions {
+    fn default() -> Self {
+        MirOptions {
+            phase: MirPhase::Optimized,
+            dump: None,
+            graphviz: false,
         }
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, strum::IntoStaticStr)]
-pub enum Mode {
-    Debug,
-    Release,
-}
+impl MirOptions {
+    /// Dumping MIR uses `-Z` flags, which only nightly accepts
+    pub fn requires_nightly(&self) -> bool {
+        self.dump_filter().is_some()
+    }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, strum::IntoStaticStr)]
-pub enum Edition {
-    Rust2015,
-    Rust2018,
-    Rust2021, // TODO - add parallel tests for 2021
-}
+    // The optimized MIR is what `--emit=mir` produces; anything else
+    // has to be dumped by the compiler while it runs the passes.
+    fn dump_filter(&self) -> Option<String> {
+        let mut filters: Vec<&str> = self.dump.iter().map(String::as_str).collect();
 
-impl Edition {
-    fn cargo_ident(&self) -> &'static str {
-        use self::Edition::*;
+        if self.phase != MirPhase::Optimized || self.graphviz {
+            filters.push(self.phase.dump_name());
+        }
 
-        match *self {
-            Rust2015 => "2015",
-            Rust2018 => "2018",
-            Rust2021 => "2021",
+        if filters.is_empty() {
+            None
+        } else {
+            Some(filters.join("|"))
         }
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, strum::IntoStaticStr)]
-pub enum CrateType {
-    Binary,
-    Library(LibraryType),
-}
+fn build_mir_dump_args(mir: &MirOptions) -> Vec<String> {
+    let mut args = Vec::new();
 
-impl CrateType {
-    fn file_name(&self) -> &'static str {
-        use self::CrateType::*;
+    if let Some(filter) = mir.dump_filter() {
+        args.push(format!("-Zdump-mir={}", filter));
+        args.push(format!(
+            "-Zdump-mir-dir=/playground-result/{}",
+            MIR_DUMP_DIR
+        ));
 
-        match *self {
-            Binary => "src/main.rs",
-            Library(_) => "src/lib.rs",
+        if mir.graphviz {
+            args.push("-Zdump-mir-graphviz".to_string());
         }
     }
+
+    args
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, strum::IntoStaticStr)]
+const MIR_DUMP_DIR: &str = "mir_dump";
+// Limits on how much of the dumped MIR is returned
+const MAX_MIR_DUMPS: usize = 200;
+const MAX_MIR_DUMP_SIZE: u64 = 8 * 1024 * 1024;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SizeAnalysisOptions {
+    /// How many of the largest symbols to return
+    pub symbol_limit: usize,
+}
+
+impl Default for SizeAnalysisOptions {
+    fn default() -> Self {
+        SizeAnalysisOptions { symbol_limit: 25 }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WasmTarget {
+    Unknown,
+    Wasi,
+}
+
+impl WasmTarget {
+    fn triple(&self) -> &'static str {
+        match *self {
+            WasmTarget::Unknown => "wasm32-unknown-unknown",
+            WasmTarget::Wasi => "wasm32-wasi",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WasmOptions {
+    pub target: WasmTarget,
+    /// Return the compiled module itself
+    pub binary: bool,
+    /// Run wasm-bindgen to generate JavaScript glue
+    pub bindgen: bool,
+    /// Run the module with a WASI runtime
+    pub execute: bool,
+}
+
+impl Default for WasmOptions {
+    fn default() -> Self {
+        WasmOptions {
+            target: WasmTarget::Unknown,
+            binary: false,
+            bindgen: false,
+            execute: false,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, strum::IntoStaticStr)]
+pub enum OptLevel {
+    Zero,
+    One,
+    Two,
+    Three,
+    Size,
+    MinSize,
+}
+
+impl OptLevel {
+    fn cargo_value(&self) -> toml::Value {
+        use self::OptLevel::*;
+
+        match *self {
+            Zero => 0.into(),
+            One => 1.into(),
+            Two => 2.into(),
+            Three => 3.into(),
+            Size => "s".into(),
+            MinSize => "z".into(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, strum::IntoStaticStr)]
+pub enum DebugInfo {
+    None,
+    Limited,
+    Full,
+}
+
+impl DebugInfo {
+    fn cargo_value(&self) -> toml::Value {
+        match *self {
+            DebugInfo::None => 0.into(),
+            DebugInfo::Limited => 1.into(),
+            DebugInfo::Full => 2.into(),
+        }
+    }
+}
+
+/// Overrides for the Cargo profile selected by the `Mode`. Settings
+/// that are not present keep the profile's defaults. They only apply
+/// to the playground crate so that the preinstalled crates don't need
+/// to be rebuilt; Cargo can't set `panic` or `lto` per package, so
+/// those aren't offered.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ProfileOptions {
+    pub opt_level: Option<OptLevel>,
+    pub debug: Option<DebugInfo>,
+    pub overflow_checks: Option<bool>,
+    pub debug_assertions: Option<bool>,
+    pub codegen_units: Option<u32>,
+}
+
+/// The MIR of one item after (or before) one compiler pass.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MirDump {
+    pub item: String,
+    pub pass: String,
+    pub mir: String,
+    pub graphviz: Option<String>,
+}
+
+impl MirDump {
+    fn is_end_of(&self, phase: MirPhase) -> bool {
+        !self.pass.ends_with(".before")
+            && self.pass.split('.').any(|part| part == phase.dump_name())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, strum::IntoStaticStr)]
+pub enum CompileTarget {
+    Assembly(AssemblyFlavor, DemangleAssembly, ProcessAssembly),
+    LlvmIr,
+    Mir,
+    Hir,
+    Wasm,
+    SizeAnalysis,
+}
+
+impl CompileTarget {
+    fn extension(&self) -> &'static OsStr {
+        let ext = match *self {
+            CompileTarget::Assembly(_, _, _) => "s",
+            CompileTarget::LlvmIr => "ll",
+            CompileTarget::Mir => "mir",
+            CompileTarget::Hir => "hir",
+            CompileTarget::Wasm => "wat",
+            CompileTarget::SizeAnalysis => "symbols",
+        };
+        OsStr::new(ext)
+    }
+}
+
+impl fmt::Display for CompileTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use self::CompileTarget::*;
+
+        match *self {
+            Assembly(_, _, _) => "assembly".fmt(f),
+            LlvmIr => "LLVM IR".fmt(f),
+            Mir => "Rust MIR".fmt(f),
+            Hir => "Rust HIR".fmt(f),
+            Wasm => "WebAssembly".fmt(f),
+            SizeAnalysis => "size analysis".fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, strum::IntoStaticStr)]
+pub enum Channel {
+    Stable,
+    Beta,
+    Nightly,
+}
+
+impl Channel {
+    fn container_name(&self) -> &'static str {
+        use self::Channel::*;
+
+        match *self {
+            Stable => "rust-stable",
+            Beta => "rust-beta",
+            Nightly => "rust-nightly",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, strum::IntoStaticStr)]
+pub enum Mode {
+    Debug,
+    Release,
+}
+
+impl Mode {
+    fn cargo_profile(&self) -> &'static str {
+        match *self {
+            Mode::Debug => "dev",
+            Mode::Release => "release",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, strum::IntoStaticStr)]
+pub enum Edition {
+    Rust2015,
+    Rust2018,
+    Rust2021, // TODO - add parallel tests for 2021
+}
+
+impl Edition {
+    fn cargo_ident(&self) -> &'static str {
+        use self::Edition::*;
+
+        match *self {
+            Rust2015 => "2015",
+            Rust2018 => "2018",
+            Rust2021 => "2021",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, strum::IntoStaticStr)]
+pub enum CrateType {
+    Binary,
+    Library(LibraryType),
+}
+
+impl CrateType {
+    fn file_name(&self) -> &'static str {
+        use self::CrateType::*;
+
+        match *self {
+            Binary => "src/main.rs",
+            Library(_) => "src/lib.rs",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, strum::IntoStaticStr)]
 pub enum LibraryType {
     Lib,
     Dylib,
@@ -917,34 +1746,153 @@ impl LibraryType {
     }
 }
 
-trait DockerCommandExt {
-    fn apply_crate_type(&mut self, req: impl CrateTypeRequest);
-    fn apply_edition(&mut self, req: impl EditionRequest);
-    fn apply_backtrace(&mut self, req: impl BacktraceRequest);
+/// Everything that the container needs to know to run one job. It is
+/// written to `JOB_SPEC_FILE`, where `modify-cargo-toml` applies it
+/// and hands the command to the entrypoint.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct JobSpec {
+    version: u32,
+    /// Seconds before the command is killed
+    timeout: u64,
+    edition: Option<&'static str>,
+    crate_type: Option<&'static str>,
+    no_dependencies: bool,
+    release_lto: bool,
+    /// Add wasm-bindgen as a dependency
+    wasm_bindgen: bool,
+    /// Where the source of the procedural macro crate is
+    proc_macro: Option<&'static str>,
+    /// A partial Cargo.toml to merge into the playground's manifest
+    manifest: Option<String>,
+    command: Vec<String>,
+    /// Variables for the command and the Cargo wrappers that it runs
+    environment: BTreeMap<&'static str, String>,
+    profile: Option<JobProfile>,
+}
+
+#[derive(Debug, Serialize)]
+struct JobProfile {
+    name: &'static str,
+    /// Keys and values as they appear in Cargo.toml
+    settings: BTreeMap<&'static str, toml::Value>,
 }
 
-impl DockerCommandExt for Command {
+impl Default for JobSpec {
+    fn default() -> Self {
+        JobSpec {
+            version: JOB_SPEC_VERSION,
+            timeout: DOCKER_PROCESS_TIMEOUT_SOFT.as_secs(),
+            edition: None,
+            crate_type: None,
+            no_dependencies: false,
+            release_lto: false,
+            wasm_bindgen: false,
+            proc_macro: None,
+            manifest: None,
+            command: Vec::new(),
+            environment: BTreeMap::new(),
+            profile: None,
+        }
+    }
+}
+
+impl JobSpec {
+    fn arg(&mut self, arg: impl Into<String>) -> &mut Self {
+        self.command.push(arg.into());
+        self
+    }
+
+    fn args<I>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.command
+            .extend(args.into_iter().map(|a| a.as_ref().to_owned()));
+        self
+    }
+
+    fn env(&mut self, key: &'static str, value: impl Into<String>) -> &mut Self {
+        self.environment.insert(key, value.into());
+        self
+    }
+
+    /// The container splits the command and environment on NUL, so
+    /// one inside a word would smuggle in extra arguments
+    fn contains_nul(&self) -> bool {
+        self.command
+            .iter()
+            .chain(self.environment.values())
+            .any(|word| word.contains('\0'))
+    }
+
     fn apply_crate_type(&mut self, req: impl CrateTypeRequest) {
         if let CrateType::Library(lib) = req.crate_type() {
-            self.args(&[
-                "--env",
-                &format!("PLAYGROUND_CRATE_TYPE={}", lib.cargo_ident()),
-            ]);
+            self.crate_type = Some(lib.cargo_ident());
         }
     }
 
     fn apply_edition(&mut self, req: impl EditionRequest) {
-        if let Some(edition) = req.edition() {
-            self.args(&[
-                "--env",
-                &format!("PLAYGROUND_EDITION={}", edition.cargo_ident()),
-            ]);
-        }
+        self.edition = req.edition().map(|e| e.cargo_ident());
     }
 
     fn apply_backtrace(&mut self, req: impl BacktraceRequest) {
         if req.backtrace() {
-            self.args(&["--env", "RUST_BACKTRACE=1"]);
+            self.env("RUST_BACKTRACE", "1");
+        }
+    }
+
+    fn apply_profile(&mut self, req: impl ProfileRequest) {
+        let ProfileOptions {
+            opt_level,
+            debug,
+            overflow_checks,
+            debug_assertions,
+            codegen_units,
+        } = req.profile();
+
+        let settings = [
+            ("opt-level", opt_level.map(|v| v.cargo_value())),
+            ("debug", debug.map(|v| v.cargo_value())),
+            ("overflow-checks", overflow_checks.map(Into::into)),
+            ("debug-assertions", debug_assertions.map(Into::into)),
+            ("codegen-units", codegen_units.map(Into::into)),
+        ];
+        let settings: BTreeMap<_, _> = IntoIterator::into_iter(settings)
+            .filter_map(|(key, value)| Some((key, value?)))
+            .collect();
+
+        if !settings.is_empty() {
+            self.profile = Some(JobProfile {
+                name: req.mode().cargo_profile(),
+                settings,
+            });
+        }
+    }
+
+    fn apply_manifest(&mut self, req: impl ManifestRequest) {
+        self.manifest = req.manifest().map(Into::into);
+    }
+
+    fn apply_proc_macro(&mut self, req: impl ProcMacroRequest) {
+        if req.proc_macro().is_some() {
+            self.proc_macro = Some(PROC_MACRO_SOURCE);
+        }
+    }
+
+    fn apply_wasm(&mut self, wasm: &WasmOptions) {
+        self.env("PLAYGROUND_WASM_TARGET", wasm.target.triple());
+
+        if wasm.binary {
+            self.env("PLAYGROUND_WASM_BINARY", "true");
+        }
+        if wasm.bindgen {
+            self.wasm_bindgen = true;
+            self.env("PLAYGROUND_WASM_BINDGEN", "true");
+        }
+        if wasm.execute {
+            self.env("PLAYGROUND_WASM_EXECUTE", "true");
         }
     }
 }
@@ -979,6 +1927,41 @@ impl<R: BacktraceRequest> BacktraceRequest for &'_ R {
     }
 }
 
+trait ProcMacroRequest {
+    fn proc_macro(&self) -> Option<&str>;
+}
+
+impl<R: ProcMacroRequest> ProcMacroRequest for &'_ R {
+    fn proc_macro(&self) -> Option<&str> {
+        (*self).proc_macro()
+    }
+}
+
+trait ProfileRequest {
+    fn mode(&self) -> Mode;
+    fn profile(&self) -> ProfileOptions;
+}
+
+impl<R: ProfileRequest> ProfileRequest for &'_ R {
+    fn mode(&self) -> Mode {
+        (*self).mode()
+    }
+
+    fn profile(&self) -> ProfileOptions {
+        (*self).profile()
+    }
+}
+
+trait ManifestRequest {
+    fn manifest(&self) -> Option<&str>;
+}
+
+impl<R: ManifestRequest> ManifestRequest for &'_ R {
+    fn manifest(&self) -> Option<&str> {
+        (*self).manifest()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CompileRequest {
     pub target: CompileTarget,
@@ -988,7 +1971,16 @@ pub struct CompileRequest {
     pub edition: Option<Edition>,
     pub tests: bool,
     pub backtrace: bool,
+    pub functions: Vec<String>,
+    pub mir: MirOptions,
+    pub wasm: WasmOptions,
+    pub size_analysis: SizeAnalysisOptions,
+    pub profile: ProfileOptions,
     pub code: String,
+    /// The source of a procedural macro crate that `code` can use
+    pub proc_macro: Option<String>,
+    /// A partial Cargo.toml to merge into the playground's manifest
+    pub manifest: Option<String>,
 }
 
 impl CrateTypeRequest for CompileRequest {
@@ -1009,14 +2001,99 @@ impl BacktraceRequest for CompileRequest {
     }
 }
 
+impl ProcMacroRequest for CompileRequest {
+    fn proc_macro(&self) -> Option<&str> {
+        self.proc_macro.as_deref()
+    }
+}
+
+impl ProfileRequest for CompileRequest {
+    fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    fn profile(&self) -> ProfileOptions {
+        self.profile
+    }
+}
+
+impl ManifestRequest for CompileRequest {
+    fn manifest(&self) -> Option<&str> {
+        self.manifest.as_deref()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CompileResponse {
     pub success: bool,
     pub code: String,
+    pub source_map: Vec<SourceMapping>,
+    pub functions: Vec<FunctionLocation>,
+    pub mir_dumps: Vec<MirDump>,
+    pub mir_graphviz: Option<String>,
+    /// The module, encoded as base64
+    pub wasm_binary: Option<String>,
+    /// The JavaScript glue generated by wasm-bindgen
+    pub wasm_js: Option<String>,
+    pub size_analysis: Option<SizeAnalysis>,
     pub stdout: String,
     pub stderr: String,
 }
 
+/// An inclusive range of 1-based line numbers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+/// Lines of the compiled output that were generated from a range of
+/// lines of the user's source code.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SourceMapping {
+    pub source: LineRange,
+    pub code: LineRange,
+}
+
+/// A function found in the compiled output. The lines are only
+/// present when the function is part of the returned code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionLocation {
+    pub name: String,
+    pub lines: Option<LineRange>,
+}
+
+// Collapses the per-line source information into runs of adjacent
+// output lines that came from the same source line.
+fn group_source_lines(source_lines: Vec<Option<u32>>) -> Vec<SourceMapping> {
+    let mut mappings: Vec<SourceMapping> = Vec::new();
+
+    for (code_line, source_line) in (1..).zip(source_lines) {
+        let source_line = match source_line {
+            Some(l) => l,
+            None => continue,
+        };
+
+        match mappings.last_mut() {
+            Some(last) if last.source.start == source_line && last.code.end + 1 == code_line => {
+                last.code.end = code_line;
+            }
+            _ => mappings.push(SourceMapping {
+                source: LineRange {
+                    start: source_line,
+                    end: source_line,
+                },
+                code: LineRange {
+                    start: code_line,
+                    end: code_line,
+                },
+            }),
+        }
+    }
+
+    mappings
+}
+
 #[derive(Debug, Clone)]
 pub struct ExecuteRequest {
     pub channel: Channel,
@@ -1024,8 +2101,17 @@ pub struct ExecuteRequest {
     pub edition: Option<Edition>,
     pub crate_type: CrateType,
     pub tests: bool,
+    /// Only run the examples in the library's documentation
+    pub doctests: bool,
+    /// Only run the test with exactly this name
+    pub test_filter: Option<String>,
     pub backtrace: bool,
+    pub profile: ProfileOptions,
     pub code: String,
+    /// The source of a procedural macro crate that `code` can use
+    pub proc_macro: Option<String>,
+    /// A partial Cargo.toml to merge into the playground's manifest
+    pub manifest: Option<String>,
 }
 
 impl CrateTypeRequest for ExecuteRequest {
@@ -1046,11 +2132,34 @@ impl BacktraceRequest for ExecuteRequest {
     }
 }
 
+impl ProcMacroRequest for ExecuteRequest {
+    fn proc_macro(&self) -> Option<&str> {
+        self.proc_macro.as_deref()
+    }
+}
+
+impl ProfileRequest for ExecuteRequest {
+    fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    fn profile(&self) -> ProfileOptions {
+        self.profile
+    }
+}
+
+impl ManifestRequest for ExecuteRequest {
+    fn manifest(&self) -> Option<&str> {
+        self.manifest.as_deref()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ExecuteResponse {
     pub success: bool,
     pub stdout: String,
     pub stderr: String,
+    pub test_results: Option<TestResults>,
 }
 
 #[derive(Debug, Clone)]
@@ -1118,10 +2227,93 @@ pub struct MiriResponse {
     pub stderr: String,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DocFormat {
+    Html,
+    /// Only available on nightly
+    Json,
+}
+
+impl DocFormat {
+    fn cargo_ident(&self) -> &'static str {
+        match self {
+            DocFormat::Html => "html",
+            DocFormat::Json => "json",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DocRequest {
+    pub channel: Channel,
+    pub edition: Option<Edition>,
+    pub crate_type: CrateType,
+    pub format: DocFormat,
+    pub code: String,
+}
+
+impl CrateTypeRequest for DocRequest {
+    fn crate_type(&self) -> CrateType {
+        self.crate_type
+    }
+}
+
+impl EditionRequest for DocRequest {
+    fn edition(&self) -> Option<Edition> {
+        self.edition
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DocResponse {
+    pub success: bool,
+    /// The generated HTML as a gzipped tarball
+    pub archive: Option<Vec<u8>>,
+    pub json: Option<String>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchmarkRequest {
+    pub code: String,
+    pub edition: Option<Edition>,
+    pub crate_type: CrateType,
+    /// How many times to run the benchmark harness
+    pub samples: u32,
+}
+
+impl CrateTypeRequest for BenchmarkRequest {
+    fn crate_type(&self) -> CrateType {
+        self.crate_type
+    }
+}
+
+impl EditionRequest for BenchmarkRequest {
+    fn edition(&self) -> Option<Edition> {
+        self.edition
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchmarkResponse {
+    pub success: bool,
+    pub benchmarks: Vec<Benchmark>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct MacroExpansionRequest {
+    pub channel: Channel,
     pub code: String,
     pub edition: Option<Edition>,
+    /// The source of a procedural macro crate that `code` can use
+    pub proc_macro: Option<String>,
+    /// Only show this item, such as `Widget` or `shapes::Circle`
+    pub item: Option<String>,
+    /// Run rustfmt over the expanded code
+    pub format: bool,
 }
 
 impl EditionRequest for MacroExpansionRequest {
@@ -1130,6 +2322,12 @@ impl EditionRequest for MacroExpansionRequest {
     }
 }
 
+impl ProcMacroRequest for MacroExpansionRequest {
+    fn proc_macro(&self) -> Option<&str> {
+        self.proc_macro.as_deref()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct MacroExpansionResponse {
     pub success: bool,
@@ -1163,6 +2361,30 @@ mod test {
     }
     "#;
 
+    #[test]
+    fn job_spec_serializes_settings() {
+        let req = ExecuteRequest {
+            mode: Mode::Release,
+            backtrace: true,
+            profile: ProfileOptions {
+                opt_level: Some(OptLevel::Size),
+                overflow_checks: Some(true),
+                ..ProfileOptions::default()
+            },
+            ..ExecuteRequest::default()
+        };
+
+        let mut job = JobSpec::default();
+        set_execution_environment(&mut job, None, &req);
+        job.args(["cargo", "run", "--release"]);
+
+        let spec = toml::to_string(&job).expect("Unable to serialize the job spec");
+        assert!(spec.contains(r#"command = ["cargo", "run", "--release"]"#));
+        assert!(spec.contains("[environment]\nRUST_BACKTRACE = \"1\""));
+        assert!(spec.contains("[profile]\nname = \"release\""));
+        assert!(spec.contains("[profile.settings]\nopt-level = \"s\"\noverflow-checks = true"));
+    }
+
     impl Default for ExecuteRequest {
         fn default() -> Self {
             ExecuteRequest {
@@ -1170,9 +2392,14 @@ mod test {
                 crate_type: CrateType::Binary,
                 mode: Mode::Debug,
                 tests: false,
+                doctests: false,
+                test_filter: None,
                 code: HELLO_WORLD_CODE.to_string(),
                 edition: None,
                 backtrace: false,
+                profile: ProfileOptions::default(),
+                proc_macro: None,
+                manifest: None,
             }
         }
     }
@@ -1188,6 +2415,26 @@ mod test {
                 code: HELLO_WORLD_CODE.to_string(),
                 edition: None,
                 backtrace: false,
+                functions: Vec::new(),
+                mir: MirOptions::default(),
+                wasm: WasmOptions::default(),
+                size_analysis: SizeAnalysisOptions::default(),
+                profile: ProfileOptions::default(),
+                proc_macro: None,
+                manifest: None,
+            }
+        }
+    }
+
+    impl Default for MacroExpansionRequest {
+        fn default() -> Self {
+            MacroExpansionRequest {
+                channel: Channel::Nightly,
+                code: HELLO_WORLD_CODE.to_string(),
+                edition: None,
+                proc_macro: None,
+                item: None,
+                format: false,
             }
         }
     }
@@ -1203,174 +2450,523 @@ mod test {
     }
 
     #[test]
-    fn basic_functionality() {
+    fn basic_functionality() {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest::default();
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.execute(&req).expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("Hello, world!"));
+    }
+
+    const COMPILATION_MODE_CODE: &'static str = r#"
+    #[cfg(debug_assertions)]
+    fn main() {
+        println!("Compiling in debug mode");
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn main() {
+        println!("Compiling in release mode");
+    }
+    "#;
+
+    #[test]
+    fn debug_mode() {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            code: COMPILATION_MODE_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.execute(&req).expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("debug mode"));
+    }
+
+    #[test]
+    fn release_mode() {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            mode: Mode::Release,
+            code: COMPILATION_MODE_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.execute(&req).expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("release mode"));
+    }
+
+    static VERSION_CODE: &'static str = r#"
+    use std::process::Command;
+
+    fn main() {
+        let output = Command::new("rustc").arg("--version").output().unwrap();
+        let output = String::from_utf8(output.stdout).unwrap();
+        println!("{}", output);
+    }
+    "#;
+
+    #[test]
+    fn stable_channel() {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            channel: Channel::Stable,
+            code: VERSION_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.execute(&req).expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("rustc"));
+        assert!(!resp.stdout.contains("beta"));
+        assert!(!resp.stdout.contains("nightly"));
+    }
+
+    #[test]
+    fn beta_channel() {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            channel: Channel::Beta,
+            code: VERSION_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.execute(&req).expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("rustc"));
+        assert!(resp.stdout.contains("beta"));
+        assert!(!resp.stdout.contains("nightly"));
+    }
+
+    #[test]
+    fn nightly_channel() {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            channel: Channel::Nightly,
+            code: VERSION_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.execute(&req).expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("rustc"));
+        assert!(!resp.stdout.contains("beta"));
+        assert!(resp.stdout.contains("nightly"));
+    }
+
+    // Code that will only work in Rust 2015
+    const EDITION_CODE: &str = r#"
+    fn main() {
+        let async = true;
+    }
+    "#;
+
+    const EDITION_ERROR: &str = "found keyword `async`";
+
+    #[test]
+    fn rust_edition_default() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            channel: Channel::Nightly,
+            code: EDITION_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+
+        assert!(!resp.stderr.contains(EDITION_ERROR), "was: {}", resp.stderr);
+        Ok(())
+    }
+
+    #[test]
+    fn rust_edition_2015() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            channel: Channel::Nightly,
+            code: EDITION_CODE.to_string(),
+            edition: Some(Edition::Rust2015),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+
+        assert!(!resp.stderr.contains(EDITION_ERROR), "was: {}", resp.stderr);
+        Ok(())
+    }
+
+    #[test]
+    fn rust_edition_2018() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            channel: Channel::Nightly,
+            code: EDITION_CODE.to_string(),
+            edition: Some(Edition::Rust2018),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+
+        assert!(resp.stderr.contains(EDITION_ERROR), "was: {}", resp.stderr);
+        Ok(())
+    }
+
+    const PROC_MACRO_CODE: &str = r#"
+    use proc_macro::TokenStream;
+    use quote::quote;
+    use syn::{parse_macro_input, DeriveInput};
+
+    #[proc_macro_derive(Describe)]
+    pub fn derive_describe(input: TokenStream) -> TokenStream {
+        let input = parse_macro_input!(input as DeriveInput);
+        let name = input.ident;
+        let description = format!("I am {}", name);
+
+        quote! {
+            impl #name {
+                fn describe() -> &'static str { #description }
+            }
+        }
+        .into()
+    }
+    "#;
+
+    const PROC_MACRO_CONSUMER_CODE: &str = r#"
+    use playground_macros::Describe;
+
+    #[derive(Describe)]
+    struct Widget;
+
+    fn main() {
+        println!("{}", Widget::describe());
+    }
+    "#;
+
+    #[test]
+    fn proc_macro_used_by_binary() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            code: PROC_MACRO_CONSUMER_CODE.to_string(),
+            proc_macro: Some(PROC_MACRO_CODE.to_string()),
+            edition: Some(Edition::Rust2018),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+
+        assert!(
+            resp.stdout.contains("I am Widget"),
+            "stderr: {}",
+            resp.stderr
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn proc_macro_expanded_in_binary() -> Result<()> {
         let _singleton = one_test_at_a_time();
-        let req = ExecuteRequest::default();
+        let req = MacroExpansionRequest {
+            code: PROC_MACRO_CONSUMER_CODE.to_string(),
+            edition: Some(Edition::Rust2018),
+            proc_macro: Some(PROC_MACRO_CODE.to_string()),
+            ..MacroExpansionRequest::default()
+        };
 
-        let sb = Sandbox::new().expect("Unable to create sandbox");
-        let resp = sb.execute(&req).expect("Unable to execute code");
+        let resp = Sandbox::new()?.macro_expansion(&req)?;
 
-        assert!(resp.stdout.contains("Hello, world!"));
+        assert!(
+            resp.stdout.contains(r#""I am Widget""#),
+            "stderr: {}",
+            resp.stderr
+        );
+        Ok(())
     }
 
-    const COMPILATION_MODE_CODE: &'static str = r#"
-    #[cfg(debug_assertions)]
+    const OVERFLOWING_CODE: &str = r#"
     fn main() {
-        println!("Compiling in debug mode");
+        let x = std::env::args().count() as u8 + 254;
+        println!("Wrapped to {}", x + 1);
     }
+    "#;
 
-    #[cfg(not(debug_assertions))]
-    fn main() {
-        println!("Compiling in release mode");
+    #[test]
+    fn profile_options_apply_to_the_selected_mode() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let req = ExecuteRequest {
+            code: r#"fn main() { debug_assert!(false, "Assertions are enabled"); }"#.to_string(),
+            mode: Mode::Release,
+            profile: ProfileOptions {
+                debug_assertions: Some(true),
+                ..ProfileOptions::default()
+            },
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+
+        assert!(
+            resp.stderr.contains("Assertions are enabled"),
+            "stderr: {}",
+            resp.stderr
+        );
+        Ok(())
     }
-    "#;
 
     #[test]
-    fn debug_mode() {
+    fn manifest_changes_profile() -> Result<()> {
         let _singleton = one_test_at_a_time();
         let req = ExecuteRequest {
-            code: COMPILATION_MODE_CODE.to_string(),
+            code: OVERFLOWING_CODE.to_string(),
+            manifest: Some("[profile.dev]\noverflow-checks = false".to_string()),
             ..ExecuteRequest::default()
         };
 
-        let sb = Sandbox::new().expect("Unable to create sandbox");
-        let resp = sb.execute(&req).expect("Unable to execute code");
+        let resp = Sandbox::new()?.execute(&req)?;
 
-        assert!(resp.stdout.contains("debug mode"));
+        assert!(
+            resp.stdout.contains("Wrapped to 0"),
+            "stderr: {}",
+            resp.stderr
+        );
+        Ok(())
     }
 
     #[test]
-    fn release_mode() {
+    fn manifest_rejects_unavailable_dependencies() -> Result<()> {
         let _singleton = one_test_at_a_time();
         let req = ExecuteRequest {
-            mode: Mode::Release,
-            code: COMPILATION_MODE_CODE.to_string(),
+            manifest: Some("[dependencies]\nnot-a-real-crate = \"*\"".to_string()),
             ..ExecuteRequest::default()
         };
 
-        let sb = Sandbox::new().expect("Unable to create sandbox");
-        let resp = sb.execute(&req).expect("Unable to execute code");
+        let resp = Sandbox::new()?.execute(&req)?;
 
-        assert!(resp.stdout.contains("release mode"));
+        assert!(!resp.success);
+        assert!(
+            resp.stderr.contains("not-a-real-crate"),
+            "stderr: {}",
+            resp.stderr
+        );
+        Ok(())
     }
 
-    static VERSION_CODE: &'static str = r#"
-    use std::process::Command;
+    const EXPANSION_CODE: &str = r#"
+    #[derive(Debug)]
+    struct Widget;
 
     fn main() {
-        let output = Command::new("rustc").arg("--version").output().unwrap();
-        let output = String::from_utf8(output.stdout).unwrap();
-        println!("{}", output);
+        println!("{:?}", Widget);
     }
     "#;
 
     #[test]
-    fn stable_channel() {
+    fn macro_expansion_on_stable() -> Result<()> {
         let _singleton = one_test_at_a_time();
-        let req = ExecuteRequest {
+        let req = MacroExpansionRequest {
             channel: Channel::Stable,
-            code: VERSION_CODE.to_string(),
-            ..ExecuteRequest::default()
+            code: EXPANSION_CODE.to_string(),
+            ..MacroExpansionRequest::default()
         };
 
-        let sb = Sandbox::new().expect("Unable to create sandbox");
-        let resp = sb.execute(&req).expect("Unable to execute code");
+        let resp = Sandbox::new()?.macro_expansion(&req)?;
 
-        assert!(resp.stdout.contains("rustc"));
-        assert!(!resp.stdout.contains("beta"));
-        assert!(!resp.stdout.contains("nightly"));
+        assert!(resp.success, "stderr: {}", resp.stderr);
+        assert!(resp.stdout.contains("impl ::core::fmt::Debug for Widget"));
+        Ok(())
     }
 
     #[test]
-    fn beta_channel() {
+    fn macro_expansion_of_one_item() -> Result<()> {
         let _singleton = one_test_at_a_time();
-        let req = ExecuteRequest {
-            channel: Channel::Beta,
-            code: VERSION_CODE.to_string(),
-            ..ExecuteRequest::default()
+        let req = MacroExpansionRequest {
+            code: EXPANSION_CODE.to_string(),
+            item: Some("Widget".to_string()),
+            format: true,
+            ..MacroExpansionRequest::default()
         };
 
-        let sb = Sandbox::new().expect("Unable to create sandbox");
-        let resp = sb.execute(&req).expect("Unable to execute code");
+        let resp = Sandbox::new()?.macro_expansion(&req)?;
 
-        assert!(resp.stdout.contains("rustc"));
-        assert!(resp.stdout.contains("beta"));
-        assert!(!resp.stdout.contains("nightly"));
+        assert!(
+            resp.stdout.starts_with("struct Widget;"),
+            "was: {}",
+            resp.stdout
+        );
+        assert!(resp.stdout.contains("impl ::core::fmt::Debug for Widget {"));
+        assert!(!resp.stdout.contains("fn main"));
+        assert!(!resp.stdout.contains("prelude_import"));
+        Ok(())
+    }
+
+    const DOC_CODE: &str = r#"
+    /// Doubles the value. See also [`halve`].
+    pub fn double(x: u8) -> u8 {
+        x * 2
+    }
+
+    /// Halves the value
+    pub fn halve(x: u8) -> u8 {
+        x / 2
     }
+    "#;
 
     #[test]
-    fn nightly_channel() {
+    fn doc_html_archived() -> Result<()> {
         let _singleton = one_test_at_a_time();
-        let req = ExecuteRequest {
+        let req = DocRequest {
+            channel: Channel::Stable,
+            edition: None,
+            crate_type: CrateType::Library(LibraryType::Lib),
+            format: DocFormat::Html,
+            code: DOC_CODE.to_string(),
+        };
+
+        let resp = Sandbox::new()?.doc(&req)?;
+
+        assert!(resp.success, "stderr: {}", resp.stderr);
+        let archive = resp.archive.expect("No documentation was archived");
+        // gzip magic number
+        assert_eq!(archive[..2], [0x1f, 0x8b]);
+        assert_eq!(resp.json, None);
+        Ok(())
+    }
+
+    #[test]
+    fn doc_json_on_nightly() -> Result<()> {
+        let _singleton = one_test_at_a_time();
+        let req = DocRequest {
             channel: Channel::Nightly,
-            code: VERSION_CODE.to_string(),
-            ..ExecuteRequest::default()
+            edition: None,
+            crate_type: CrateType::Library(LibraryType::Lib),
+            format: DocFormat::Json,
+            code: DOC_CODE.to_string(),
         };
 
-        let sb = Sandbox::new().expect("Unable to create sandbox");
-        let resp = sb.execute(&req).expect("Unable to execute code");
+        let resp = Sandbox::new()?.doc(&req)?;
 
-        assert!(resp.stdout.contains("rustc"));
-        assert!(!resp.stdout.contains("beta"));
-        assert!(resp.stdout.contains("nightly"));
+        let json = resp.json.expect("No JSON documentation was created");
+        assert!(
+            json.contains("Doubles the value"),
+            "stderr: {}",
+            resp.stderr
+        );
+        assert_eq!(resp.archive, None);
+        Ok(())
     }
 
-    // Code that will only work in Rust 2015
-    const EDITION_CODE: &str = r#"
-    fn main() {
-        let async = true;
+    const TEST_CODE: &str = r#"
+    #[test]
+    fn passes() {
+        println!("from the passing test");
     }
-    "#;
 
-    const EDITION_ERROR: &str = "found keyword `async`";
+    #[test]
+    fn fails() {
+        assert_eq!(1, 2);
+    }
+    "#;
 
     #[test]
-    fn rust_edition_default() -> Result<()> {
+    fn test_results_structured() -> Result<()> {
         let _singleton = one_test_at_a_time();
         let req = ExecuteRequest {
-            channel: Channel::Nightly,
-            code: EDITION_CODE.to_string(),
+            code: TEST_CODE.to_string(),
+            tests: true,
             ..ExecuteRequest::default()
         };
 
         let resp = Sandbox::new()?.execute(&req)?;
-
-        assert!(!resp.stderr.contains(EDITION_ERROR), "was: {}", resp.stderr);
+        let results = resp.test_results.expect("Tests were not run");
+
+        let statuses: Vec<_> = results
+            .tests
+            .iter()
+            .map(|t| (t.name.as_str(), t.status))
+            .collect();
+        assert!(statuses.contains(&("passes", TestStatus::Passed)));
+        assert!(statuses.contains(&("fails", TestStatus::Failed)));
+
+        let passes = results.tests.iter().find(|t| t.name == "passes").unwrap();
+        assert_eq!(passes.output.as_deref(), Some("from the passing test"));
+        assert_eq!((results.summary.passed, results.summary.failed), (1, 1));
         Ok(())
     }
 
+    const DOCTEST_CODE: &str = r#"
+    /// ```
+    /// assert_eq!(playground::double(2), 4);
+    /// ```
+    pub fn double(x: u8) -> u8 {
+        x * 2
+    }
+
     #[test]
-    fn rust_edition_2015() -> Result<()> {
+    fn unit_test() {}
+    "#;
+
+    #[test]
+    fn doctests_attributed_to_items() -> Result<()> {
         let _singleton = one_test_at_a_time();
         let req = ExecuteRequest {
-            channel: Channel::Nightly,
-            code: EDITION_CODE.to_string(),
-            edition: Some(Edition::Rust2015),
+            code: DOCTEST_CODE.to_string(),
+            crate_type: CrateType::Library(LibraryType::Lib),
+            tests: true,
+            doctests: true,
             ..ExecuteRequest::default()
         };
 
         let resp = Sandbox::new()?.execute(&req)?;
+        let results = resp.test_results.expect("Tests were not run");
 
-        assert!(!resp.stderr.contains(EDITION_ERROR), "was: {}", resp.stderr);
+        assert_eq!(results.tests.len(), 1, "stdout: {}", resp.stdout);
+        let test = &results.tests[0];
+        assert_eq!(test.status, TestStatus::Passed);
+        let item = test.doc_item.as_ref().expect("Not a doctest");
+        assert_eq!((item.name.as_str(), item.line), ("double", 2));
         Ok(())
     }
 
     #[test]
-    fn rust_edition_2018() -> Result<()> {
+    fn test_filter_runs_one_test() -> Result<()> {
         let _singleton = one_test_at_a_time();
         let req = ExecuteRequest {
-            channel: Channel::Nightly,
-            code: EDITION_CODE.to_string(),
-            edition: Some(Edition::Rust2018),
+            code: TEST_CODE.to_string(),
+            tests: true,
+            test_filter: Some("passes".to_string()),
             ..ExecuteRequest::default()
         };
 
         let resp = Sandbox::new()?.execute(&req)?;
+        let results = resp.test_results.expect("Tests were not run");
 
-        assert!(resp.stderr.contains(EDITION_ERROR), "was: {}", resp.stderr);
+        assert!(resp.success, "stdout: {}", resp.stdout);
+        assert_eq!(results.tests.len(), 1);
+        assert_eq!(results.summary.filtered_out, 1);
         Ok(())
     }
 
+    #[test]
+    fn test_filter_with_nul_rejected() {
+        let req = ExecuteRequest {
+            code: TEST_CODE.to_string(),
+            tests: true,
+            test_filter: Some("passes\0--nocapture".to_string()),
+            ..ExecuteRequest::default()
+        };
+
+        let error = Sandbox::new()
+            .and_then(|sb| sb.execute(&req))
+            .expect_err("The filter should be rejected");
+        assert!(matches!(error, Error::JobSpecContainsNul), "{}", error);
+    }
+
     const BACKTRACE_CODE: &str = r#"
     fn trigger_the_problem() {
         None::<u8>.unwrap();
@@ -1448,6 +3044,153 @@ mod test {
         assert!(resp.code.contains("target triple"));
     }
 
+    #[test]
+    fn output_llvm_ir_selected_function() {
+        let _singleton = one_test_at_a_time();
+        let code = r#"
+        fn helper() -> u8 { 42 }
+        fn main() { println!("{}", helper()); }
+        "#;
+        let req = CompileRequest {
+            target: CompileTarget::LlvmIr,
+            functions: vec!["helper".to_string()],
+            code: code.to_string(),
+            ..CompileRequest::default()
+        };
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.compile(&req).expect("Unable to compile code");
+
+        assert!(resp.code.contains("playground6helper"));
+        assert!(!resp.code.contains("playground4main"));
+        assert!(resp
+            .functions
+            .iter()
+            .any(|f| f.name == "playground::main" && f.lines.is_none()));
+    }
+
+    #[test]
+    fn output_mir_dumps() {
+        let _singleton = one_test_at_a_time();
+        let req = CompileRequest {
+            target: CompileTarget::Mir,
+            channel: Channel::Nightly,
+            mir: MirOptions {
+                phase: MirPhase::Built,
+                dump: Some("main".to_string()),
+                graphviz: true,
+            },
+            ..CompileRequest::default()
+        };
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.compile(&req).expect("Unable to compile code");
+
+        assert!(resp.code.contains("fn main()"));
+        assert!(resp.mir_graphviz.unwrap_or_default().contains("digraph"));
+        assert!(resp.mir_dumps.iter().any(|d| d.item == "main"));
+    }
+
+    #[test]
+    fn mir_phase_found_by_dump_name() {
+        let dump = |pass: &str| MirDump {
+            item: "main".into(),
+            pass: pass.into(),
+            mir: String::new(),
+            graphviz: None,
+        };
+
+        assert!(dump("2-1-000.analysis.after").is_end_of(MirPhase::Analysis));
+        assert!(!dump("2-2-000.analysis-post-cleanup.after").is_end_of(MirPhase::Analysis));
+        assert!(!dump("2-1-004.SimplifyCfg-post-analysis.after").is_end_of(MirPhase::Analysis));
+        assert!(dump("3-3-000.runtime-optimized.after").is_end_of(MirPhase::Optimized));
+        assert!(!dump("1-1-000.built.before").is_end_of(MirPhase::Built));
+    }
+
+    #[test]
+    fn mir_dumps_limited() {
+        let dir = tempdir::TempDir::new("playground").expect("Unable to create a directory");
+        for i in 0..MAX_MIR_DUMPS + 5 {
+            let name = format!(
+                "playground.item{:03}.1-1-006.SimplifyCfg-initial.after.mir",
+                i
+            );
+            std::fs::write(dir.path().join(name), "fn item() {}").unwrap();
+        }
+        // Sorts last, but is needed to show the built MIR
+        std::fs::write(
+            dir.path().join("playground.main.1-1-000.built.after.mir"),
+            "fn main() {}",
+        )
+        .unwrap();
+
+        let runtime = Builder::new_current_thread().build().unwrap();
+        let (dumps, truncated) = runtime
+            .block_on(fut::read_mir_dumps(dir.path(), MirPhase::Built))
+            .expect("Unable to read the dumps");
+
+        assert!(truncated);
+        assert_eq!(dumps.len(), MAX_MIR_DUMPS);
+        assert!(dumps
+            .iter()
+            .any(|d| d.item == "main" && d.mir == "fn main() {}"));
+    }
+
+    #[test]
+    fn output_wasm_executed_with_wasi() {
+        let _singleton = one_test_at_a_time();
+        let req = CompileRequest {
+            target: CompileTarget::Wasm,
+            channel: Channel::Nightly,
+            wasm: WasmOptions {
+                target: WasmTarget::Wasi,
+                binary: true,
+                bindgen: false,
+                execute: true,
+            },
+            ..CompileRequest::default()
+        };
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.compile(&req).expect("Unable to compile code");
+
+        assert!(resp.stdout.contains("Hello, world!"));
+        assert!(resp.wasm_binary.is_some());
+    }
+
+    #[test]
+    fn output_size_analysis() {
+        let _singleton = one_test_at_a_time();
+        let req = CompileRequest {
+            target: CompileTarget::SizeAnalysis,
+            ..CompileRequest::default()
+        };
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.compile(&req).expect("Unable to compile code");
+        let analysis = resp.size_analysis.expect("No size analysis");
+
+        assert!(analysis.sections.iter().any(|s| s.name == ".text"));
+        assert!(analysis.crates.iter().any(|c| c.name == "std"));
+        assert_eq!(analysis.symbols.len(), req.size_analysis.symbol_limit);
+    }
+
+    #[test]
+    fn output_formatted_hir() {
+        let _singleton = one_test_at_a_time();
+        let req = CompileRequest {
+            target: CompileTarget::Hir,
+            channel: Channel::Nightly,
+            ..CompileRequest::default()
+        };
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.compile(&req).expect("Unable to compile code");
+
+        assert!(resp.code.contains("fn main()"));
+        assert!(!resp.stderr.contains("Unable to format"));
+    }
+
     #[test]
     fn output_assembly() {
         let _singleton = one_test_at_a_time();
@@ -1508,6 +3251,54 @@ mod test {
         assert!(resp.code.contains(".file"));
     }
 
+    #[test]
+    fn output_assembly_source_map() {
+        let _singleton = one_test_at_a_time();
+        let req = CompileRequest {
+            target: CompileTarget::Assembly(
+                AssemblyFlavor::Att,
+                DemangleAssembly::Demangle,
+                ProcessAssembly::Filter,
+            ),
+            mode: Mode::Release,
+            ..CompileRequest::default()
+        };
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.compile(&req).expect("Unable to compile code");
+
+        assert!(resp.source_map.iter().any(|m| m.source.start == 3));
+    }
+
+    #[test]
+    fn benchmark_samples_summarized() {
+        let _singleton = one_test_at_a_time();
+        let code = r#"
+        #![feature(test)]
+        extern crate test;
+
+        #[bench]
+        fn bench_sum(b: &mut test::Bencher) {
+            b.iter(|| (0..100u64).sum::<u64>());
+        }
+
+        fn main() {}
+        "#;
+        let req = BenchmarkRequest {
+            code: code.to_string(),
+            edition: None,
+            crate_type: CrateType::Binary,
+            samples: 3,
+        };
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.benchmark(&req).expect("Unable to run benchmarks");
+
+        assert_eq!(resp.benchmarks.len(), 1);
+        assert_eq!(resp.benchmarks[0].name, "bench_sum");
+        assert_eq!(resp.benchmarks[0].samples, 3);
+    }
+
     #[test]
     fn formatting_code() {
         let _singleton = one_test_at_a_time();
@@ -1,17 +1,23 @@
 use crate::{
     gist,
+    hosted_docs::HostedDocs,
+    limits::{self, Limits, SourceCode},
     metrics::{
         track_metric_async, track_metric_force_endpoint_async, track_metric_no_request_async,
         Endpoint, GenerateLabels, SuccessDetails,
     },
     sandbox::{self, fut::Sandbox, Channel},
-    CachingSnafu, ClippyRequest, ClippyResponse, CompilationSnafu, CompileRequest, CompileResponse,
-    Config, Error, ErrorJson, EvaluateRequest, EvaluateResponse, EvaluationSnafu, ExecuteRequest,
-    ExecuteResponse, ExecutionSnafu, ExpansionSnafu, FormatRequest, FormatResponse,
-    FormattingSnafu, GhToken, GistCreationSnafu, GistLoadingSnafu, InterpretingSnafu, LintingSnafu,
-    MacroExpansionRequest, MacroExpansionResponse, MetaCratesResponse, MetaGistCreateRequest,
-    MetaGistResponse, MetaVersionResponse, MetricsToken, MiriRequest, MiriResponse, Result,
-    SandboxCreationSnafu, ONE_HOUR, SANDBOX_CACHE_TIME_TO_LIVE,
+    BenchmarkRequest, BenchmarkResponse, BenchmarkingSnafu, CachingSnafu, ClippyRequest,
+    ClippyResponse, CompilationSnafu, CompileRequest, CompileResponse, Config, DocRequest,
+    DocResponse, DocumentingSnafu, Error, ErrorJson, EvaluateRequest, EvaluateResponse,
+    EvaluationSnafu, ExecuteRequest, ExecuteResponse, ExecutionSnafu, ExpansionSnafu,
+    FormatRequest, FormatResponse, FormattingSnafu, GhToken, GistCreationSnafu, GistLoadingSnafu,
+    InterpretingSnafu, LintingSnafu, MacroExpansionRequest, MacroExpansionResponse,
+    MetaCrateDependenciesResponse, MetaCrateDependentsResponse, MetaCratesResponse,
+    MetaGistCreateRequest, MetaGistResponse, MetaVersionResponse, MetricsToken, MiriRequest,
+    MiriResponse, RequestRejectedSnafu, Result, SandboxCreationSnafu, DESERIALIZATION_ERROR_CODE,
+    HOSTED_DOCS_PATH, HOSTED_DOCS_SWEEP_INTERVAL, HOSTED_DOCS_TIME_TO_LIVE, ONE_HOUR,
+    SANDBOX_CACHE_TIME_TO_LIVE,
 };
 use async_trait::async_trait;
 use axum::{
@@ -41,18 +47,29 @@ use tower_http::{
     trace::TraceLayer,
 };
 
+const MAX_AGE_ONE_HOUR: HeaderValue = HeaderValue::from_static("public, max-age=3600");
 const MAX_AGE_ONE_DAY: HeaderValue = HeaderValue::from_static("public, max-age=86400");
 const MAX_AGE_ONE_YEAR: HeaderValue = HeaderValue::from_static("public, max-age=31536000");
 
+const SANDBOXED_CONTENT: HeaderValue = HeaderValue::from_static("sandbox");
+const NO_SNIFF: HeaderValue = HeaderValue::from_static("nosniff");
+
 #[tokio::main]
 pub(crate) async fn serve(config: Config) {
     let root_files = static_file_service(config.root_path(), MAX_AGE_ONE_DAY);
     let asset_files = static_file_service(config.asset_path(), MAX_AGE_ONE_YEAR);
+
+    let hosted_docs = HostedDocs::new(HOSTED_DOCS_TIME_TO_LIVE)
+        .expect("Unable to create the hosted documentation directory");
+    let hosted_docs = Arc::new(hosted_docs);
+    let doc_files = hosted_docs_service(hosted_docs.root(), MAX_AGE_ONE_HOUR);
+    tokio::spawn(sweep_hosted_docs(hosted_docs.clone()));
     let rewrite_help_as_index = middleware::from_fn(rewrite_help_as_index);
 
     let mut app = Router::new()
         .fallback(root_files)
         .nest("/assets", asset_files)
+        .nest(HOSTED_DOCS_PATH, doc_files)
         .layer(rewrite_help_as_index)
         .route("/evaluate.json", post(evaluate))
         .route("/compile", post(compile))
@@ -60,8 +77,15 @@ pub(crate) async fn serve(config: Config) {
         .route("/format", post(format))
         .route("/clippy", post(clippy))
         .route("/miri", post(miri))
+        .route("/doc", post(doc))
         .route("/macro-expansion", post(macro_expansion))
+        .route("/benchmark", post(benchmark))
         .route("/meta/crates", get_or_post(meta_crates))
+        .route(
+            "/meta/crates/:name/dependencies",
+            get(meta_crate_dependencies),
+        )
+        .route("/meta/crates/:name/dependents", get(meta_crate_dependents))
         .route("/meta/version/stable", get_or_post(meta_version_stable))
         .route("/meta/version/beta", get_or_post(meta_version_beta))
         .route("/meta/version/nightly", get_or_post(meta_version_nightly))
@@ -72,7 +96,9 @@ pub(crate) async fn serve(config: Config) {
         .route("/meta/gist/:id", get(meta_gist_get))
         .route("/metrics", get(metrics))
         .layer(AddExtensionLayer::new(Arc::new(SandboxCache::default())))
-        .layer(AddExtensionLayer::new(config.github_token()));
+        .layer(AddExtensionLayer::new(hosted_docs))
+        .layer(AddExtensionLayer::new(config.github_token()))
+        .layer(AddExtensionLayer::new(config.limits()));
 
     if let Some(token) = config.metrics_token() {
         app = app.layer(AddExtensionLayer::new(token))
@@ -115,6 +141,42 @@ fn static_file_service(root: impl AsRef<path::Path>, max_age: HeaderValue) -> Me
     })
 }
 
+/// The hosted documentation is generated from user code and can
+/// contain arbitrary scripts, so it is kept from acting as the
+/// playground itself.
+fn hosted_docs_service(root: impl AsRef<path::Path>, max_age: HeaderValue) -> MethodRouter {
+    let files = ServeDir::new(root);
+
+    let with_caching = SetResponseHeader::if_not_present(files, header::CACHE_CONTROL, max_age);
+    let sandboxed = SetResponseHeader::overriding(
+        with_caching,
+        header::CONTENT_SECURITY_POLICY,
+        SANDBOXED_CONTENT,
+    );
+    let no_sniff =
+        SetResponseHeader::overriding(sandboxed, header::X_CONTENT_TYPE_OPTIONS, NO_SNIFF);
+
+    get_service(no_sniff).handle_error(|e| async move {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unhandled internal error: {}", e),
+        )
+    })
+}
+
+async fn sweep_hosted_docs(hosted_docs: Arc<HostedDocs>) {
+    let mut interval = tokio::time::interval(HOSTED_DOCS_SWEEP_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let hosted_docs = hosted_docs.clone();
+        if let Err(e) = tokio::task::spawn_blocking(move || hosted_docs.remove_expired()).await {
+            log::warn!("Unable to remove expired hosted docs: {}", e);
+        }
+    }
+}
+
 async fn rewrite_help_as_index<B>(
     mut req: Request<B>,
     next: middleware::Next<B>,
@@ -192,6 +254,25 @@ async fn miri(Json(req): Json<MiriRequest>) -> Result<Json<MiriResponse>> {
     .map(Json)
 }
 
+async fn doc(
+    Extension(hosted_docs): Extension<Arc<HostedDocs>>,
+    Json(req): Json<DocRequest>,
+) -> Result<Json<DocResponse>> {
+    let resp = with_sandbox(
+        req,
+        |sb, req| async move { sb.doc(req).await }.boxed(),
+        DocumentingSnafu,
+    )
+    .await?;
+
+    // Unpacking the archive and clearing out old documentation both
+    // touch the filesystem
+    tokio::task::spawn_blocking(move || DocResponse::hosted(resp, &hosted_docs))
+        .await
+        .expect("Hosting the documentation panicked")
+        .map(Json)
+}
+
 async fn macro_expansion(
     Json(req): Json<MacroExpansionRequest>,
 ) -> Result<Json<MacroExpansionResponse>> {
@@ -204,6 +285,16 @@ async fn macro_expansion(
     .map(Json)
 }
 
+async fn benchmark(Json(req): Json<BenchmarkRequest>) -> Result<Json<BenchmarkResponse>> {
+    with_sandbox(
+        req,
+        |sb, req| async move { sb.benchmark(req).await }.boxed(),
+        BenchmarkingSnafu,
+    )
+    .await
+    .map(Json)
+}
+
 async fn with_sandbox<F, Req, Resp, SbReq, SbResp, Ctx>(req: Req, f: F, ctx: Ctx) -> Result<Resp>
 where
     for<'req> F: FnOnce(Sandbox, &'req SbReq) -> BoxFuture<'req, sandbox::Result<SbResp>>,
@@ -249,6 +340,30 @@ async fn meta_crates(
         .map(Json)
 }
 
+async fn meta_crate_dependencies(
+    Extension(cache): Extension<Arc<SandboxCache>>,
+    Path(name): Path<String>,
+) -> Result<Json<MetaCrateDependenciesResponse>> {
+    track_metric_no_request_async(Endpoint::MetaCrateDependencies, || async {
+        let graph = cache.dependency_graph().await?;
+        MetaCrateDependenciesResponse::lookup(&graph, &name)
+    })
+    .await
+    .map(Json)
+}
+
+async fn meta_crate_dependents(
+    Extension(cache): Extension<Arc<SandboxCache>>,
+    Path(name): Path<String>,
+) -> Result<Json<MetaCrateDependentsResponse>> {
+    track_metric_no_request_async(Endpoint::MetaCrateDependents, || async {
+        let graph = cache.dependency_graph().await?;
+        MetaCrateDependentsResponse::lookup(&graph, &name)
+    })
+    .await
+    .map(Json)
+}
+
 async fn meta_version_stable(
     Extension(cache): Extension<Arc<SandboxCache>>,
 ) -> Result<Json<MetaVersionResponse>> {
@@ -376,6 +491,7 @@ struct SandboxCache {
     // change them to contain `Arc`s. e.g., `Vec<Foo>` could become
     // `Arc<[Foo]>`.
     crates: CacheOne<MetaCratesResponse>,
+    dependency_graph: CacheOne<Arc<sandbox::DependencyGraph>>,
     version_stable: CacheOne<MetaVersionResponse>,
     version_beta: CacheOne<MetaVersionResponse>,
     version_nightly: CacheOne<MetaVersionResponse>,
@@ -393,6 +509,15 @@ impl SandboxCache {
             .await
     }
 
+    async fn dependency_graph(&self) -> Result<Arc<sandbox::DependencyGraph>> {
+        self.dependency_graph
+            .fetch(|sandbox| async move {
+                let graph = sandbox.dependency_graph().await.context(CachingSnafu)?;
+                Ok(Arc::new(graph))
+            })
+            .await
+    }
+
     async fn version_stable(&self) -> Result<MetaVersionResponse> {
         self.version_stable
             .fetch(|sandbox| async move {
@@ -523,21 +648,22 @@ impl<T> CacheInfo<T> {
 
 impl IntoResponse for Error {
     fn into_response(self) -> axum::response::Response {
-        Json(ErrorJson {
-            error: self.to_string(),
-        })
-        .into_response()
+        let status =
+            StatusCode::from_u16(self.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+
+        (status, Json(ErrorJson::from(self))).into_response()
     }
 }
 
 /// This type only exists so that we can recover from the `axum::Json`
-/// error and format it using our expected JSON error object.
+/// error and format it using our expected JSON error object. It also
+/// applies the request limits before the code is used.
 struct Json<T>(T);
 
 #[async_trait]
 impl<T, B> extract::FromRequest<B> for Json<T>
 where
-    T: serde::de::DeserializeOwned,
+    T: serde::de::DeserializeOwned + SourceCode,
     B: axum::body::HttpBody + Send,
     B::Data: Send,
     B::Error: Into<axum::BoxError>,
@@ -545,16 +671,101 @@ where
     type Rejection = axum::response::Response;
 
     async fn from_request(req: &mut extract::RequestParts<B>) -> Result<Self, Self::Rejection> {
-        match axum::Json::<T>::from_request(req).await {
-            Ok(v) => Ok(Self(v.0)),
-            Err(e) => {
-                let error = format!("Unable to deserialize request: {e}");
-                Err(axum::Json(ErrorJson { error }).into_response())
+        let limits = req
+            .extensions()
+            .and_then(|e| e.get::<Limits>())
+            .copied()
+            .unwrap_or_default();
+
+        let body = read_limited_body(req, &limits).await?;
+
+        // Hand the checked body back to `axum::Json` so that it can
+        // verify the content type and deserialize as usual.
+        let mut request = Request::new(axum::body::Full::new(axum::body::Bytes::from(body)));
+        if let Some(headers) = req.headers() {
+            *request.headers_mut() = headers.clone();
+        }
+
+        match axum::Json::<T>::from_request(&mut extract::RequestParts::new(request)).await {
+            Ok(v) => {
+                limits
+                    .check_code(&v.0)
+                    .context(RequestRejectedSnafu)
+                    .map_err(IntoResponse::into_response)?;
+                Ok(Self(v.0))
             }
+            Err(e) => Err(deserialization_error(format!(
+                "Unable to deserialize request: {e}"
+            ))),
         }
     }
 }
 
+async fn read_limited_body<B>(
+    req: &mut extract::RequestParts<B>,
+    limits: &Limits,
+) -> Result<Vec<u8>, axum::response::Response>
+where
+    B: axum::body::HttpBody + Send,
+    B::Data: Send,
+    B::Error: Into<axum::BoxError>,
+{
+    use bytes::Buf;
+
+    let rejected = |e: limits::Error| RequestRejectedSnafu.into_error(e).into_response();
+
+    // Reject oversized requests without reading them when the client
+    // tells us how large they are.
+    let content_length = req
+        .headers()
+        .and_then(|h| h.get(header::CONTENT_LENGTH))
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+    if let Some(content_length) = content_length {
+        limits
+            .check_request_size(content_length)
+            .map_err(rejected)?;
+    }
+
+    let body = match req.take_body() {
+        Some(body) => body,
+        None => {
+            return Err(deserialization_error(
+                "Unable to deserialize request: the body was already used".into(),
+            ))
+        }
+    };
+    let mut body = Box::pin(body);
+
+    let mut bytes = Vec::new();
+    while let Some(chunk) = axum::body::HttpBody::data(&mut body).await {
+        let mut chunk = chunk
+            .map_err(|e| deserialization_error(format!("Unable to read request: {}", e.into())))?;
+
+        while chunk.has_remaining() {
+            let part = chunk.chunk();
+            bytes.extend_from_slice(part);
+            let len = part.len();
+            chunk.advance(len);
+        }
+
+        limits.check_request_size(bytes.len()).map_err(rejected)?;
+    }
+
+    limits.check_request(&bytes).map_err(rejected)?;
+
+    Ok(bytes)
+}
+
+fn deserialization_error(error: String) -> axum::response::Response {
+    let body = axum::Json(ErrorJson {
+        error,
+        code: DESERIALIZATION_ERROR_CODE,
+    });
+
+    (StatusCode::BAD_REQUEST, body).into_response()
+}
+
 impl<T> IntoResponse for Json<T>
 where
     T: serde::Serialize,
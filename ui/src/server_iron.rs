@@ -1,15 +1,20 @@
 use crate::{
     gist,
+    hosted_docs::HostedDocs,
+    limits::{self, Limits, SourceCode},
     metrics::{self, track_metric, track_metric_force_endpoint, track_metric_no_request},
     sandbox::{self, Sandbox},
-    CachingSnafu, ClippyRequest, ClippyResponse, CompilationSnafu, CompileRequest, CompileResponse,
-    Config, DeserializationSnafu, Error, ErrorJson, EvaluateRequest, EvaluateResponse,
-    EvaluationSnafu, ExecuteRequest, ExecuteResponse, ExecutionSnafu, ExpansionSnafu,
-    FormatRequest, FormatResponse, FormattingSnafu, GhToken, InterpretingSnafu, LintingSnafu,
-    MacroExpansionRequest, MacroExpansionResponse, MetaCratesResponse, MetaGistCreateRequest,
-    MetaGistResponse, MetaVersionResponse, MetricsToken, MiriRequest, MiriResponse, Result,
-    SandboxCreationSnafu, SerializationSnafu, FATAL_ERROR_JSON, ONE_DAY, ONE_HOUR_IN_SECONDS,
-    ONE_YEAR, SANDBOX_CACHE_TIME_TO_LIVE,
+    BenchmarkRequest, BenchmarkResponse, BenchmarkingSnafu, CachingSnafu, ClippyRequest,
+    ClippyResponse, CompilationSnafu, CompileRequest, CompileResponse, Config,
+    DeserializationSnafu, DocRequest, DocResponse, DocumentingSnafu, Error, ErrorJson,
+    EvaluateRequest, EvaluateResponse, EvaluationSnafu, ExecuteRequest, ExecuteResponse,
+    ExecutionSnafu, ExpansionSnafu, FormatRequest, FormatResponse, FormattingSnafu, GhToken,
+    InterpretingSnafu, LintingSnafu, MacroExpansionRequest, MacroExpansionResponse,
+    MetaCrateDependenciesResponse, MetaCrateDependentsResponse, MetaCratesResponse,
+    MetaGistCreateRequest, MetaGistResponse, MetaVersionResponse, MetricsToken, MiriRequest,
+    MiriResponse, RequestRejectedSnafu, Result, SandboxCreationSnafu, SerializationSnafu,
+    FATAL_ERROR_JSON, HOSTED_DOCS_PATH, HOSTED_DOCS_SWEEP_INTERVAL, HOSTED_DOCS_TIME_TO_LIVE,
+    ONE_DAY, ONE_HOUR, ONE_HOUR_IN_SECONDS, ONE_YEAR, SANDBOX_CACHE_TIME_TO_LIVE,
 };
 use corsware::{AllowedOrigins, CorsMiddleware, UniCase};
 use iron::{
@@ -27,13 +32,21 @@ use playground_middleware::{
 use prometheus::{Encoder, TextEncoder};
 use router::Router;
 use serde::{de::DeserializeOwned, Serialize};
-use snafu::ResultExt;
-use std::{any::Any, convert::TryInto, sync::Mutex, time::Instant};
+use snafu::{IntoError, ResultExt};
+use std::{
+    any::Any,
+    convert::TryInto,
+    io,
+    sync::{Arc, Mutex},
+    thread,
+    time::Instant,
+};
 
 pub(crate) fn serve(config: Config) {
     let Config {
         root,
         gh_token,
+        limits,
         address,
         port,
         logfile,
@@ -49,6 +62,33 @@ pub(crate) fn serve(config: Config) {
     files.link_after(Prefix::new(&["assets"], Cache::new(ONE_YEAR)));
     files.link_after(GuessContentType::new(ContentType::html().0));
 
+    let doc_files = Staticfile::new(hosted_docs().root())
+        .expect("Unable to open hosted documentation directory");
+    let mut doc_files = Chain::new(doc_files);
+
+    doc_files.link_after(ModifyWith::new(Cache::new(ONE_HOUR)));
+    doc_files.link_after(GuessContentType::new(ContentType::html().0));
+    doc_files.link_after(SandboxHostedDocs);
+
+    thread::spawn(|| loop {
+        thread::sleep(HOSTED_DOCS_SWEEP_INTERVAL);
+        hosted_docs().remove_expired();
+    });
+
+    let mut crates_router = Router::new();
+    crates_router.get("/", meta_crates, "crates_get");
+    crates_router.post("/", meta_crates, "crates_post");
+    crates_router.get(
+        "/:name/dependencies",
+        meta_crate_dependencies,
+        "crate_dependencies",
+    );
+    crates_router.get(
+        "/:name/dependents",
+        meta_crate_dependents,
+        "crate_dependents",
+    );
+
     let mut gist_router = Router::new();
     gist_router.post("/", meta_gist_create, "gist_create");
     gist_router.get("/:id", meta_gist_get, "gist_get");
@@ -60,8 +100,11 @@ pub(crate) fn serve(config: Config) {
     mount.mount("/format", format);
     mount.mount("/clippy", clippy);
     mount.mount("/miri", miri);
+    mount.mount("/doc", doc);
+    mount.mount(HOSTED_DOCS_PATH, doc_files);
     mount.mount("/macro-expansion", macro_expansion);
-    mount.mount("/meta/crates", meta_crates);
+    mount.mount("/benchmark", benchmark);
+    mount.mount("/meta/crates", crates_router);
     mount.mount("/meta/version/stable", meta_version_stable);
     mount.mount("/meta/version/beta", meta_version_beta);
     mount.mount("/meta/version/nightly", meta_version_nightly);
@@ -82,6 +125,10 @@ pub(crate) fn serve(config: Config) {
     chain.link_around(logger);
     chain.link_before(rewrite);
     chain.link_before(gh_token);
+    chain.link_before(limits);
+    chain.link_before(persistent::Read::<bodyparser::MaxBodyLength>::one(
+        limits.request_size,
+    ));
 
     if let Some(metrics_token) = metrics_token {
         let metrics_token = MetricsToken::new(metrics_token);
@@ -109,6 +156,32 @@ pub(crate) fn serve(config: Config) {
         .expect("Unable to start server");
 }
 
+/// The hosted documentation is generated from user code and can
+/// contain arbitrary scripts, so it is kept from acting as the
+/// playground itself.
+struct SandboxHostedDocs;
+
+impl SandboxHostedDocs {
+    fn set_headers(res: &mut Response) {
+        res.headers
+            .set_raw("Content-Security-Policy", vec![b"sandbox".to_vec()]);
+        res.headers
+            .set_raw("X-Content-Type-Options", vec![b"nosniff".to_vec()]);
+    }
+}
+
+impl iron::AfterMiddleware for SandboxHostedDocs {
+    fn after(&self, _req: &mut Request<'_, '_>, mut res: Response) -> IronResult<Response> {
+        Self::set_headers(&mut res);
+        Ok(res)
+    }
+
+    fn catch(&self, _req: &mut Request<'_, '_>, mut err: IronError) -> IronResult<Response> {
+        Self::set_headers(&mut err.response);
+        Err(err)
+    }
+}
+
 impl iron::BeforeMiddleware for GhToken {
     fn before(&self, req: &mut Request<'_, '_>) -> IronResult<()> {
         req.extensions.insert::<Self>(self.clone());
@@ -120,6 +193,17 @@ impl iron::typemap::Key for GhToken {
     type Value = Self;
 }
 
+impl iron::BeforeMiddleware for Limits {
+    fn before(&self, req: &mut Request<'_, '_>) -> IronResult<()> {
+        req.extensions.insert::<Self>(*self);
+        Ok(())
+    }
+}
+
+impl iron::typemap::Key for Limits {
+    type Value = Self;
+}
+
 impl iron::BeforeMiddleware for MetricsToken {
     fn before(&self, req: &mut Request<'_, '_>) -> IronResult<()> {
         req.extensions.insert::<Self>(self.clone());
@@ -176,6 +260,14 @@ fn miri(req: &mut Request<'_, '_>) -> IronResult<Response> {
     })
 }
 
+fn doc(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox(req, |sandbox, req: DocRequest| {
+        let req = req.try_into()?;
+        let resp = track_metric(req, |req| sandbox.doc(req)).context(DocumentingSnafu)?;
+        DocResponse::hosted(resp, hosted_docs())
+    })
+}
+
 fn macro_expansion(req: &mut Request<'_, '_>) -> IronResult<Response> {
     with_sandbox(req, |sandbox, req: MacroExpansionRequest| {
         let req = req.try_into()?;
@@ -185,6 +277,15 @@ fn macro_expansion(req: &mut Request<'_, '_>) -> IronResult<Response> {
     })
 }
 
+fn benchmark(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox(req, |sandbox, req: BenchmarkRequest| {
+        let req = req.try_into()?;
+        track_metric(req, |req| sandbox.benchmark(req))
+            .map(BenchmarkResponse::from)
+            .context(BenchmarkingSnafu)
+    })
+}
+
 fn meta_crates(_req: &mut Request<'_, '_>) -> IronResult<Response> {
     with_sandbox_no_request(|sandbox| {
         track_metric_no_request(metrics::Endpoint::MetaCrates, || cached(sandbox).crates())
@@ -192,6 +293,30 @@ fn meta_crates(_req: &mut Request<'_, '_>) -> IronResult<Response> {
     })
 }
 
+fn meta_crate_dependencies(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    match req.extensions.get::<Router>().unwrap().find("name") {
+        Some(name) => with_sandbox_no_request(|sandbox| {
+            track_metric_no_request(metrics::Endpoint::MetaCrateDependencies, || {
+                let graph = cached(sandbox).dependency_graph()?;
+                MetaCrateDependenciesResponse::lookup(&graph, name)
+            })
+        }),
+        None => Ok(Response::with(status::UnprocessableEntity)),
+    }
+}
+
+fn meta_crate_dependents(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    match req.extensions.get::<Router>().unwrap().find("name") {
+        Some(name) => with_sandbox_no_request(|sandbox| {
+            track_metric_no_request(metrics::Endpoint::MetaCrateDependents, || {
+                let graph = cached(sandbox).dependency_graph()?;
+                MetaCrateDependentsResponse::lookup(&graph, name)
+            })
+        }),
+        None => Ok(Response::with(status::UnprocessableEntity)),
+    }
+}
+
 fn meta_version_stable(_req: &mut Request<'_, '_>) -> IronResult<Response> {
     with_sandbox_no_request(|sandbox| {
         track_metric_no_request(metrics::Endpoint::MetaVersionStable, || {
@@ -281,7 +406,7 @@ fn evaluate(req: &mut Request<'_, '_>) -> IronResult<Response> {
 fn with_sandbox<Req, Resp, F>(req: &mut Request<'_, '_>, f: F) -> IronResult<Response>
 where
     F: FnOnce(Sandbox, Req) -> Result<Resp>,
-    Req: DeserializeOwned + Clone + Any + 'static,
+    Req: DeserializeOwned + SourceCode + Clone + Any + 'static,
     Resp: Serialize,
 {
     serialize_to_response(run_handler(req, f))
@@ -298,7 +423,7 @@ where
 fn run_handler<Req, Resp, F>(req: &mut Request<'_, '_>, f: F) -> Result<Resp>
 where
     F: FnOnce(Sandbox, Req) -> Result<Resp>,
-    Req: DeserializeOwned + Clone + Any + 'static,
+    Req: DeserializeOwned + SourceCode + Clone + Any + 'static,
 {
     deserialize_from_request(req, |req| {
         let sandbox = Sandbox::new().context(SandboxCreationSnafu)?;
@@ -309,19 +434,39 @@ where
 fn deserialize_from_request<Req, Resp, F>(req: &mut Request<'_, '_>, f: F) -> Result<Resp>
 where
     F: FnOnce(Req) -> Result<Resp>,
-    Req: DeserializeOwned + Clone + Any + 'static,
+    Req: DeserializeOwned + SourceCode + Clone + Any + 'static,
 {
+    let limits = req.extensions.get::<Limits>().copied().unwrap_or_default();
+
     let body = req
         .get::<bodyparser::Struct<Req>>()
-        .context(DeserializationSnafu)?;
+        .map_err(|e| body_error(&limits, e))?;
 
     let req = body.ok_or(Error::RequestMissing)?;
 
+    limits.check_code(&req).context(RequestRejectedSnafu)?;
+
     let resp = f(req)?;
 
     Ok(resp)
 }
 
+/// `bodyparser` enforces the request size and UTF-8 checks but reports
+/// them as general deserialization errors.
+fn body_error(limits: &Limits, e: bodyparser::BodyError) -> Error {
+    use bodyparser::BodyErrorCause::*;
+
+    let rejection = match &e.cause {
+        IoError(io) if io.kind() == io::ErrorKind::InvalidInput => limits::Error::RequestTooLarge {
+            limit: limits.request_size,
+        },
+        Utf8Error(utf8) => limits::Error::RequestNotUtf8 { source: *utf8 },
+        _ => return DeserializationSnafu.into_error(e),
+    };
+
+    RequestRejectedSnafu.into_error(rejection)
+}
+
 fn run_handler_no_request<Resp, F>(f: F) -> Result<Resp>
 where
     F: FnOnce(Sandbox) -> Result<Resp>,
@@ -347,12 +492,11 @@ where
             body,
         ))),
         Err(err) => {
-            let err = ErrorJson {
-                error: err.to_string(),
-            };
+            let status = status::Status::from_u16(err.status_code());
+            let err = ErrorJson::from(err);
             match serde_json::ser::to_string(&err) {
                 Ok(error_str) => Ok(Response::with((
-                    status::InternalServerError,
+                    status,
                     Header(ContentType::json()),
                     error_str,
                 ))),
@@ -422,6 +566,7 @@ where
 #[derive(Debug, Default)]
 struct SandboxCache {
     crates: SandboxCacheOne<Vec<sandbox::CrateInformation>>,
+    dependency_graph: SandboxCacheOne<Arc<sandbox::DependencyGraph>>,
     version_stable: SandboxCacheOne<sandbox::Version>,
     version_beta: SandboxCacheOne<sandbox::Version>,
     version_nightly: SandboxCacheOne<sandbox::Version>,
@@ -443,6 +588,12 @@ impl<'a> CachedSandbox<'a> {
             .clone_or_populate(|| self.sandbox.crates())
     }
 
+    fn dependency_graph(&self) -> Result<Arc<sandbox::DependencyGraph>> {
+        self.cache
+            .dependency_graph
+            .clone_or_populate(|| self.sandbox.dependency_graph().map(Arc::new))
+    }
+
     fn version_stable(&self) -> Result<sandbox::Version> {
         self.cache
             .version_stable
@@ -480,6 +631,15 @@ impl<'a> CachedSandbox<'a> {
     }
 }
 
+fn hosted_docs() -> &'static HostedDocs {
+    lazy_static! {
+        static ref HOSTED_DOCS: HostedDocs = HostedDocs::new(HOSTED_DOCS_TIME_TO_LIVE)
+            .expect("Unable to create the hosted documentation directory");
+    }
+
+    &HOSTED_DOCS
+}
+
 /// A convenience constructor
 fn cached(sandbox: Sandbox) -> CachedSandbox<'static> {
     lazy_static! {
@@ -0,0 +1,235 @@
+// Breaks down the size of a compiled artifact by section, symbol and
+// crate, in the spirit of `cargo bloat`
+
+use crate::asm_cleanup::demangle_symbol;
+use rustc_demangle::try_demangle;
+use std::{collections::BTreeMap, fmt::Write};
+
+// Symbols that aren't Rust, such as those from libc, or whose crate
+// can't be determined
+const UNKNOWN_CRATE: &str = "[Unknown]";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeAnalysis {
+    pub sections: Vec<Section>,
+    pub symbols: Vec<Symbol>,
+    pub crates: Vec<CrateSize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub name: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub crate_name: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrateSize {
+    pub name: String,
+    pub size: u64,
+}
+
+// Parses the output of `size -A -d`:
+//
+// playground  :
+// section      size     addr
+// .text      251402    28480
+// Total     4312098
+fn parse_sections(output: &str) -> Vec<Section> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let name = parts.next()?;
+            let size = parts.next()?.parse().ok()?;
+            let _addr: u64 = parts.next()?.parse().ok()?;
+
+            Some(Section {
+                name: name.to_string(),
+                size,
+            })
+        })
+        .collect()
+}
+
+// Parses the output of `nm --print-size --radix=d`:
+//
+// 0000000000028480 0000000000000047 T _ZN10playground4main17h0123456789abcdefE
+fn parse_symbols(output: &str) -> Vec<Symbol> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let _addr = parts.next()?;
+            let size = parts.next()?.parse().ok()?;
+            let _kind = parts.next()?;
+            let mangled = parts.next()?;
+
+            Some(Symbol {
+                name: demangle_symbol(mangled),
+                crate_name: crate_name(mangled),
+                size,
+            })
+        })
+        .collect()
+}
+
+// The first path segment of the demangled name. For trait
+// implementations, this is the crate of the implementing type:
+// `<playground::Foo as core::fmt::Debug>::fmt` belongs to `playground`.
+fn crate_name(mangled: &str) -> String {
+    let demangled = match try_demangle(mangled) {
+        Ok(d) => format!("{:#}", d),
+        Err(_) => return UNKNOWN_CRATE.to_string(),
+    };
+
+    let path = demangled.trim_start_matches(['<', '&', '*']);
+    let path = path
+        .strip_prefix("mut ")
+        .or_else(|| path.strip_prefix("const "))
+        .unwrap_or(path);
+    let segment = path.split("::").next().unwrap_or("");
+
+    let is_identifier = !segment.is_empty()
+        && segment.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && !segment.starts_with(|c: char| c.is_ascii_digit());
+
+    if is_identifier {
+        segment.to_string()
+    } else {
+        UNKNOWN_CRATE.to_string()
+    }
+}
+
+pub fn analyze(sections: &str, symbols: &str, symbol_limit: usize) -> SizeAnalysis {
+    let sections = parse_sections(sections);
+    let mut symbols = parse_symbols(symbols);
+
+    let mut crates = BTreeMap::new();
+    for symbol in &symbols {
+        *crates.entry(symbol.crate_name.clone()).or_insert(0) += symbol.size;
+    }
+    let mut crates: Vec<_> = crates
+        .into_iter()
+        .map(|(name, size)| CrateSize { name, size })
+        .collect();
+    crates.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+
+    symbols.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    symbols.truncate(symbol_limit);
+
+    SizeAnalysis {
+        sections,
+        symbols,
+        crates,
+    }
+}
+
+// A plain-text version of the analysis for display
+pub fn report(analysis: &SizeAnalysis) -> String {
+    let total: u64 = analysis.crates.iter().map(|c| c.size).sum();
+    let percent = |size: u64| {
+        if total == 0 {
+            0.0
+        } else {
+            size as f64 * 100.0 / total as f64
+        }
+    };
+
+    let mut report = String::new();
+
+    writeln!(report, "Sections").expect("Unable to write to a string");
+    for section in &analysis.sections {
+        writeln!(report, "{:>12}  {}", section.size, section.name)
+            .expect("Unable to write to a string");
+    }
+
+    writeln!(report, "\nCrates").expect("Unable to write to a string");
+    for c in &analysis.crates {
+        writeln!(
+            report,
+            "{:>12}  {:>5.1}%  {}",
+            c.size,
+            percent(c.size),
+            c.name
+        )
+        .expect("Unable to write to a string");
+    }
+
+    writeln!(report, "\nLargest symbols").expect("Unable to write to a string");
+    for symbol in &analysis.symbols {
+        writeln!(
+            report,
+            "{:>12}  {:>5.1}%  {}",
+            symbol.size,
+            percent(symbol.size),
+            symbol.name
+        )
+        .expect("Unable to write to a string");
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sections_parsed() {
+        let output = "playground  :\nsection      size     addr\n.text      251402    28480\n.data         120   512000\nTotal     4312098\n";
+        assert_eq!(
+            parse_sections(output),
+            [
+                Section {
+                    name: ".text".to_string(),
+                    size: 251402
+                },
+                Section {
+                    name: ".data".to_string(),
+                    size: 120
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn symbols_attributed_to_crates() {
+        assert_eq!(
+            crate_name("_ZN10playground4main17h0123456789abcdefE"),
+            "playground"
+        );
+        assert_eq!(
+            crate_name(
+                "_ZN52_$LT$playground..Foo$u20$as$u20$core..fmt..Debug$GT$3fmt17h0123456789abcdefE"
+            ),
+            "playground"
+        );
+        assert_eq!(crate_name("memcpy"), UNKNOWN_CRATE);
+    }
+
+    #[test]
+    fn analysis_sorted_and_limited() {
+        let symbols = "0000000000000001 0000000000000010 T _ZN10playground4main17h0123456789abcdefE\n0000000000000002 0000000000000300 T _ZN4core3fmt5write17h0123456789abcdefE\n0000000000000003 0000000000000020 T memcpy\n";
+        let analysis = analyze("", symbols, 2);
+
+        let names: Vec<_> = analysis.symbols.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["core::fmt::write", "memcpy"]);
+
+        let crates: Vec<_> = analysis
+            .crates
+            .iter()
+            .map(|c| (c.name.as_str(), c.size))
+            .collect();
+        assert_eq!(
+            crates,
+            [("core", 300), (UNKNOWN_CRATE, 20), ("playground", 10)]
+        );
+    }
+}
@@ -0,0 +1,307 @@
+// Extracts the individual test outcomes from libtest's human-readable
+// output. The text format is the only one available on every channel,
+// so it's parsed even when nightly could provide JSON.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestResults {
+    pub tests: Vec<TestCase>,
+    pub summary: TestSummary,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestCase {
+    pub name: String,
+    pub status: TestStatus,
+    /// Seconds; only reported when `--report-time` is enabled
+    pub duration: Option<f64>,
+    pub output: Option<String>,
+    /// For doctests, the item whose documentation contains the example
+    pub doc_item: Option<DocItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocItem {
+    /// The path to the item, such as `Stack::push`. Examples in the
+    /// crate-level documentation use the crate's name.
+    pub name: String,
+    /// The line in the source file where the example begins
+    pub line: u32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TestStatus {
+    Passed,
+    Failed,
+    Ignored,
+}
+
+/// Totals across every test binary that was run, such as the unit
+/// tests and doctests of a library.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct TestSummary {
+    pub passed: u32,
+    pub failed: u32,
+    pub ignored: u32,
+    pub measured: u32,
+    pub filtered_out: u32,
+}
+
+const SUCCESSES_HEADER: &str = "successes:";
+const FAILURES_HEADER: &str = "failures:";
+
+/// Returns `None` when no tests were run at all, usually because the
+/// code failed to compile.
+pub fn parse(stdout: &str) -> Option<TestResults> {
+    let mut ran_tests = false;
+    let mut tests = Vec::new();
+    let mut summary = TestSummary::default();
+    let mut outputs: Vec<(String, String)> = Vec::new();
+    let mut current_output: Option<(String, String)> = None;
+
+    for line in stdout.lines() {
+        if let Some(name) = output_header(line) {
+            outputs.extend(current_output.take());
+            current_output = Some((name.to_string(), String::new()));
+            continue;
+        }
+
+        if let Some((_, output)) = &mut current_output {
+            if line == SUCCESSES_HEADER || line == FAILURES_HEADER {
+                outputs.extend(current_output.take());
+            } else {
+                output.push_str(line);
+                output.push('\n');
+            }
+            continue;
+        }
+
+        if line.starts_with("running ") {
+            ran_tests = true;
+        } else if let Some(counts) = line.strip_prefix("test result: ") {
+            add_counts(&mut summary, counts);
+        } else if let Some(test) = test_line(line) {
+            tests.push(test);
+        }
+    }
+    outputs.extend(current_output);
+
+    if !ran_tests {
+        return None;
+    }
+
+    for (name, output) in outputs {
+        if let Some(test) = tests.iter_mut().find(|t| t.name == name) {
+            let output = output.trim_end_matches('\n');
+            if !output.is_empty() {
+                test.output = Some(output.to_string());
+            }
+        }
+    }
+
+    Some(TestResults { tests, summary })
+}
+
+// ---- tests::it_works stdout ----
+fn output_header(line: &str) -> Option<&str> {
+    line.strip_prefix("---- ")?.strip_suffix(" stdout ----")
+}
+
+// test tests::it_works ... ok
+// test tests::it_works ... ok <0.001s>
+// test tests::it_fails - should panic ... FAILED
+// test tests::slow ... ignored, takes too long
+fn test_line(line: &str) -> Option<TestCase> {
+    let (name, result) = line.strip_prefix("test ")?.split_once(" ... ")?;
+    let name = name.strip_suffix(" - should panic").unwrap_or(name);
+
+    let status = if result.starts_with("ok") {
+        TestStatus::Passed
+    } else if result.starts_with("FAILED") {
+        TestStatus::Failed
+    } else if result.starts_with("ignored") {
+        TestStatus::Ignored
+    } else {
+        return None;
+    };
+
+    let duration = result
+        .rsplit_once('<')
+        .and_then(|(_, time)| time.strip_suffix("s>"))
+        .and_then(|time| time.parse().ok());
+
+    Some(TestCase {
+        name: name.to_string(),
+        status,
+        duration,
+        output: None,
+        doc_item: doc_item(name),
+    })
+}
+
+// src/lib.rs - Stack::push (line 12)
+// src/lib.rs - Stack::push (line 20) - compile fail
+fn doc_item(name: &str) -> Option<DocItem> {
+    let (_file, rest) = name.split_once(" - ")?;
+    let (item, rest) = rest.split_once(" (line ")?;
+    let (line, _) = rest.split_once(')')?;
+
+    Some(DocItem {
+        name: item.to_string(),
+        line: line.parse().ok()?,
+    })
+}
+
+// ok. 2 passed; 1 failed; 0 ignored; 0 measured; 0 filtered out; finished in 0.00s
+fn add_counts(summary: &mut TestSummary, counts: &str) {
+    let counts = counts.split_once(". ").map_or(counts, |(_, c)| c);
+
+    for count in counts.split("; ") {
+        let (value, kind) = match count.split_once(' ') {
+            Some(parts) => parts,
+            None => continue,
+        };
+        let value: u32 = match value.parse() {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+
+        match kind {
+            "passed" => summary.passed += value,
+            "failed" => summary.failed += value,
+            "ignored" => summary.ignored += value,
+            "measured" => summary.measured += value,
+            "filtered out" => summary.filtered_out += value,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn individual_results_and_output() {
+        let stdout = r#"
+running 4 tests
+test tests::adds ... ok <0.001s>
+test tests::slow ... ignored, takes too long
+test tests::panics - should panic ... ok <0.002s>
+test tests::subtracts ... FAILED <0.001s>
+
+successes:
+
+---- tests::adds stdout ----
+checking addition
+
+successes:
+    tests::adds
+    tests::panics
+
+failures:
+
+---- tests::subtracts stdout ----
+thread 'tests::subtracts' panicked at 'assertion failed: 1 - 1 == 1', src/lib.rs:9:9
+
+failures:
+    tests::subtracts
+
+test result: FAILED. 2 passed; 1 failed; 1 ignored; 0 measured; 3 filtered out; finished in 0.00s
+"#;
+        let results = parse(stdout).unwrap();
+
+        let summary: Vec<_> = results
+            .tests
+            .iter()
+            .map(|t| (t.name.as_str(), t.status, t.duration))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("tests::adds", TestStatus::Passed, Some(0.001)),
+                ("tests::slow", TestStatus::Ignored, None),
+                ("tests::panics", TestStatus::Passed, Some(0.002)),
+                ("tests::subtracts", TestStatus::Failed, Some(0.001)),
+            ]
+        );
+
+        assert_eq!(
+            results.tests[0].output.as_deref(),
+            Some("checking addition")
+        );
+        assert_eq!(results.tests[1].output, None);
+        assert!(results.tests[3]
+            .output
+            .as_deref()
+            .unwrap()
+            .contains("assertion failed"));
+
+        assert_eq!(
+            results.summary,
+            TestSummary {
+                passed: 2,
+                failed: 1,
+                ignored: 1,
+                measured: 0,
+                filtered_out: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn summary_combines_test_binaries() {
+        let stdout = r#"
+running 1 test
+test tests::unit ... ok
+
+test result: ok. 1 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out; finished in 0.00s
+
+
+running 1 test
+test src/lib.rs - add (line 3) ... ok
+
+test result: ok. 1 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out; finished in 0.21s
+"#;
+        let results = parse(stdout).unwrap();
+
+        assert_eq!(results.tests.len(), 2);
+        assert_eq!(results.tests[1].name, "src/lib.rs - add (line 3)");
+        assert_eq!(results.summary.passed, 2);
+    }
+
+    #[test]
+    fn doctests_attributed_to_items() {
+        let stdout = r#"
+running 3 tests
+test src/lib.rs - Stack::push (line 12) ... ok
+test src/lib.rs - Stack::push (line 20) - compile fail ... ok
+test src/lib.rs - playground (line 1) ... FAILED
+
+test result: FAILED. 2 passed; 1 failed; 0 ignored; 0 measured; 0 filtered out; finished in 0.42s
+"#;
+        let results = parse(stdout).unwrap();
+
+        let items: Vec<_> = results
+            .tests
+            .iter()
+            .map(|t| {
+                let item = t.doc_item.as_ref().unwrap();
+                (item.name.as_str(), item.line)
+            })
+            .collect();
+        assert_eq!(
+            items,
+            [("Stack::push", 12), ("Stack::push", 20), ("playground", 1)]
+        );
+    }
+
+    #[test]
+    fn unit_tests_have_no_doc_item() {
+        assert_eq!(doc_item("tests::it_works"), None);
+    }
+
+    #[test]
+    fn nothing_run() {
+        assert_eq!(parse(""), None);
+    }
+}